@@ -0,0 +1,123 @@
+use bevy::prelude::*;
+use big_brain::prelude::*;
+
+use crate::components::{
+    ai::{
+        hostility::Provoked,
+        movement::{ start_action, stop_action, MoveStatus, MoveTo, ResumesPatrol },
+        ReflectAiComponent,
+    },
+    health::DamageEvent,
+    predefinedpath::PatrolSuspended,
+};
+
+/// How far the target moves before the actor heads for its new position
+const REFOLLOW_DISTANCE: f32 = 16.0;
+
+#[derive(Reflect, Clone, Component, Debug, ActionBuilder)]
+#[reflect(AiComponent)]
+pub(crate) struct Attack {
+    /// Running speed toward the target
+    pub speed: f32,
+    /// Distance from which the target is hit
+    pub reach: f32,
+    pub damage: u32,
+    /// Seconds between two hits
+    pub cooldown: f32,
+}
+
+/// Time until the next hit of an [`Attack`] action
+#[derive(Component, Debug)]
+pub(crate) struct AttackCooldown(pub Timer);
+
+/// Runs after whoever [`Provoked`] the actor and hits them, until it calms down
+pub(crate) fn attack_action_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    actors: Query<(&GlobalTransform, Option<&Provoked>, Option<&MoveTo>, Has<PatrolSuspended>)>,
+    targets: Query<&GlobalTransform>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut query: Query<
+        (
+            Entity,
+            &Actor,
+            &mut ActionState,
+            &Attack,
+            Option<&mut AttackCooldown>,
+            &ActionSpan,
+            Has<ResumesPatrol>,
+        )
+    >
+) {
+    for (action, Actor(actor), mut state, attack, mut cooldown, span, resumes_patrol) in &mut query {
+        let _guard = span.span().enter();
+
+        let Ok((transform, provoked, move_to, patrol_suspended)) = actors.get(*actor) else {
+            continue;
+        };
+        match *state {
+            ActionState::Requested => {
+                debug!("Fighting back!");
+                start_action(&mut commands, *actor, action, patrol_suspended);
+                // Winds up before the first hit
+                commands
+                    .entity(action)
+                    .insert(AttackCooldown(Timer::from_seconds(attack.cooldown, TimerMode::Once)));
+                *state = ActionState::Executing;
+            }
+            ActionState::Executing => {
+                let target = provoked.and_then(|provoked| {
+                    targets.get(provoked.by).ok().map(|target| (provoked.by, target))
+                });
+                let Some((target, target_transform)) = target else {
+                    debug!("Calmed down");
+                    stop_action(&mut commands, *actor, action, resumes_patrol);
+                    *state = ActionState::Success;
+                    continue;
+                };
+                let position = transform.translation().truncate();
+                let target_position = target_transform.translation().truncate();
+                let ready = cooldown
+                    .as_mut()
+                    .is_some_and(|cooldown| cooldown.0.tick(time.delta()).finished());
+
+                if position.distance(target_position) <= attack.reach {
+                    if move_to.is_some() {
+                        commands.entity(*actor).remove::<MoveTo>();
+                    }
+                    if let Some(cooldown) = cooldown.as_mut().filter(|_| ready) {
+                        trace!("Hitting {:?}", target);
+                        damage_events.send(DamageEvent {
+                            target,
+                            amount: attack.damage,
+                            source: Some(*actor),
+                        });
+                        cooldown.0.reset();
+                    }
+                    continue;
+                }
+                match move_to {
+                    Some(move_to) if move_to.status == MoveStatus::Blocked => {
+                        debug!("Could not reach {:?}", target);
+                        stop_action(&mut commands, *actor, action, resumes_patrol);
+                        *state = ActionState::Failure;
+                    }
+                    Some(move_to) if move_to.target.distance(target_position) <= REFOLLOW_DISTANCE => {
+                        trace!("Running after {:?}...", target);
+                    }
+                    _ => {
+                        commands
+                            .entity(*actor)
+                            .insert(MoveTo::new(target_position, attack.speed).with_tolerance(attack.reach / 2.0));
+                    }
+                }
+            }
+            ActionState::Cancelled => {
+                debug!("Action was cancelled. Considering this a failure.");
+                stop_action(&mut commands, *actor, action, resumes_patrol);
+                *state = ActionState::Failure;
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use bevy::prelude::*;
+use big_brain::prelude::*;
+
+use crate::components::{
+    ai::{
+        movement::{ start_action, stop_action, MoveStatus, MoveTo, ResumesPatrol },
+        threats::Threats,
+        ReflectAiComponent,
+    },
+    predefinedpath::PatrolSuspended,
+};
+
+#[derive(Reflect, Clone, Component, Debug, ActionBuilder)]
+#[reflect(AiComponent)]
+pub(crate) struct Dodge {
+    /// Distance from the actor threats count within
+    pub radius: f32,
+    /// Seconds ahead threats are anticipated
+    pub horizon: f32,
+    /// How far the actor runs
+    pub distance: f32,
+    pub speed: f32,
+}
+
+/// Runs away from where the player is about to land, or an attack comes from
+pub(crate) fn dodge_action_system(
+    mut commands: Commands,
+    threats: Threats,
+    actors: Query<(&GlobalTransform, Option<&MoveTo>, Has<PatrolSuspended>)>,
+    mut query: Query<(Entity, &Actor, &mut ActionState, &Dodge, &ActionSpan, Has<ResumesPatrol>)>
+) {
+    for (action, Actor(actor), mut state, dodge, span, resumes_patrol) in &mut query {
+        let _guard = span.span().enter();
+
+        let Ok((transform, move_to, patrol_suspended)) = actors.get(*actor) else {
+            continue;
+        };
+        match *state {
+            ActionState::Requested => {
+                let position = transform.translation().truncate();
+                let Some(threat) = threats.incoming(*actor, position, dodge.radius, dodge.horizon) else {
+                    debug!("The threat is over already");
+                    *state = ActionState::Success;
+                    continue;
+                };
+                // Away from the threat, to the right when right under it
+                let side = if position.x < threat.x { -1.0 } else { 1.0 };
+                let target = Vec2::new(position.x + side * dodge.distance, position.y);
+                debug!("Dodging a threat from {}, to {}", threat, target);
+                commands.entity(*actor).insert(MoveTo::new(target, dodge.speed));
+                start_action(&mut commands, *actor, action, patrol_suspended);
+                *state = ActionState::Executing;
+            }
+            ActionState::Executing => {
+                match move_to.map(|move_to| move_to.status) {
+                    Some(MoveStatus::Moving) => trace!("Dodging..."),
+                    Some(MoveStatus::Blocked) => {
+                        debug!("Cornered");
+                        stop_action(&mut commands, *actor, action, resumes_patrol);
+                        *state = ActionState::Failure;
+                    }
+                    Some(MoveStatus::Arrived) | None => {
+                        debug!("Dodged");
+                        stop_action(&mut commands, *actor, action, resumes_patrol);
+                        *state = ActionState::Success;
+                    }
+                }
+            }
+            ActionState::Cancelled => {
+                debug!("Action was cancelled. Considering this a failure.");
+                stop_action(&mut commands, *actor, action, resumes_patrol);
+                *state = ActionState::Failure;
+            }
+            _ => {}
+        }
+    }
+}
@@ -1,53 +1,116 @@
 use bevy::prelude::*;
 use big_brain::prelude::*;
 
-use crate::components::ai::thirst::Thirst;
+use crate::components::{
+    ai::{
+        movement::{ start_action, stop_action, MoveStatus, MoveTo, ResumesPatrol },
+        ReflectAiComponent,
+        thirst::Thirst,
+    },
+    predefinedpath::PatrolSuspended,
+    swimming::Swimmable,
+};
+
+/// Horizontal distance from which an actor can drink from a water cell
+const DRINK_REACH: f32 = 20.0;
+/// How far above or below the actor water is still reachable
+const DRINK_HEIGHT: f32 = 40.0;
 
 #[derive(Reflect, Clone, Component, Debug, ActionBuilder)]
+#[reflect(AiComponent)]
 pub(crate) struct Drink {
     pub until: f32,
     pub per_second: f32,
+    /// Walking speed toward the water
+    pub speed: f32,
+}
+
+fn in_reach(position: Vec2, water: Vec2) -> bool {
+    (water.x - position.x).abs() <= DRINK_REACH && (water.y - position.y).abs() <= DRINK_HEIGHT
+}
+
+/// The closest water cell, whether there is a path to it is only known once
+/// the [`MoveTo`] toward it gets its `PathResult`
+fn nearest_water(position: Vec2, waters: &Query<&GlobalTransform, With<Swimmable>>) -> Option<Vec2> {
+    waters
+        .iter()
+        .map(|transform| transform.translation().truncate())
+        .filter(|water| (water.y - position.y).abs() <= DRINK_HEIGHT)
+        .min_by(|a, b| a.distance_squared(position).total_cmp(&b.distance_squared(position)))
 }
 
 // Action systems execute according to a state machine, where the states are
 // labeled by ActionState.
 pub(crate) fn drink_action_system(
+    mut commands: Commands,
     time: Res<Time>,
-    mut thirsts: Query<&mut Thirst>,
+    mut actors: Query<(&mut Thirst, &GlobalTransform, Option<&MoveTo>, Has<PatrolSuspended>)>,
+    waters: Query<&GlobalTransform, With<Swimmable>>,
     // We execute actions by querying for their associated Action Component
     // (Drink in this case). You'll always need both Actor and ActionState.
-    mut query: Query<(&Actor, &mut ActionState, &Drink, &ActionSpan)>
+    mut query: Query<(Entity, &Actor, &mut ActionState, &Drink, &ActionSpan, Has<ResumesPatrol>)>
 ) {
-    for (Actor(actor), mut state, drink, span) in &mut query {
+    for (action, Actor(actor), mut state, drink, span, resumes_patrol) in &mut query {
         // This sets up the tracing scope. Any `debug` calls here will be
         // spanned together in the output.
         let _guard = span.span().enter();
 
         // Use the drink_action's actor to look up the corresponding Thirst Component.
-        if let Ok(mut thirst) = thirsts.get_mut(*actor) {
-            match *state {
-                ActionState::Requested => {
-                    debug!("Time to drink some water!");
-                    *state = ActionState::Executing;
-                }
-                ActionState::Executing => {
-                    trace!("Drinking...");
-                    thirst.thirst -=
-                        drink.per_second * ((time.delta().as_micros() as f32) / 1_000_000.0);
-                    if thirst.thirst <= drink.until {
-                        // To "finish" an action, we set its state to Success or
-                        // Failure.
-                        debug!("Done drinking water");
-                        *state = ActionState::Success;
+        let Ok((mut thirst, transform, move_to, patrol_suspended)) = actors.get_mut(*actor) else {
+            continue;
+        };
+        let position = transform.translation().truncate();
+        match *state {
+            ActionState::Requested => {
+                let Some(water) = nearest_water(position, &waters) else {
+                    debug!("No water around, giving up");
+                    *state = ActionState::Failure;
+                    continue;
+                };
+                debug!("Time to drink some water! Heading to {}", water);
+                commands
+                    .entity(*actor)
+                    .insert(MoveTo::new(water, drink.speed).with_tolerance(DRINK_REACH / 2.0));
+                start_action(&mut commands, *actor, action, patrol_suspended);
+                *state = ActionState::Executing;
+            }
+            ActionState::Executing => {
+                let at_water = waters
+                    .iter()
+                    .any(|water| in_reach(position, water.translation().truncate()));
+                if !at_water {
+                    // Blocked as well when the path search finds no way there
+                    match move_to.map(|move_to| move_to.status) {
+                        Some(MoveStatus::Moving) => trace!("Walking to the water..."),
+                        status => {
+                            debug!("Could not reach the water ({:?})", status);
+                            stop_action(&mut commands, *actor, action, resumes_patrol);
+                            *state = ActionState::Failure;
+                        }
                     }
+                    continue;
                 }
-                // All Actions should make sure to handle cancellations!
-                ActionState::Cancelled => {
-                    debug!("Action was cancelled. Considering this a failure.");
-                    *state = ActionState::Failure;
+                if move_to.is_some() {
+                    commands.entity(*actor).remove::<MoveTo>();
                 }
-                _ => {}
+                trace!("Drinking...");
+                thirst.thirst -=
+                    drink.per_second * ((time.delta().as_micros() as f32) / 1_000_000.0);
+                if thirst.thirst <= drink.until {
+                    // To "finish" an action, we set its state to Success or
+                    // Failure.
+                    debug!("Done drinking water");
+                    stop_action(&mut commands, *actor, action, resumes_patrol);
+                    *state = ActionState::Success;
+                }
+            }
+            // All Actions should make sure to handle cancellations!
+            ActionState::Cancelled => {
+                debug!("Action was cancelled. Considering this a failure.");
+                stop_action(&mut commands, *actor, action, resumes_patrol);
+                *state = ActionState::Failure;
             }
+            _ => {}
         }
     }
 }
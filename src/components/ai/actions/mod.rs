@@ -3,4 +3,7 @@
 // to make your Action Component implement the ActionBuilder trait.
 // You need your type to implement Clone and Debug (necessary for ActionBuilder)
 
+pub(crate) mod attack;
+pub(crate) mod dodge;
 pub(crate) mod drink;
+pub(crate) mod sleep;
@@ -0,0 +1,117 @@
+use bevy::prelude::*;
+use big_brain::prelude::*;
+
+use crate::{
+    components::{
+        ai::{
+            fatigue::{ Asleep, Disturbed, Fatigue },
+            movement::{ start_action, stop_action, MoveStatus, MoveTo, ResumesPatrol },
+            ReflectAiComponent,
+        },
+        predefinedpath::PatrolSuspended,
+    },
+    entities::restspot::RestSpot,
+};
+
+/// How far away a rest spot is still worth walking to
+const REST_SPOT_RANGE: f32 = 400.0;
+/// How far above or below the actor a rest spot is still reachable
+const REST_SPOT_HEIGHT: f32 = 24.0;
+
+#[derive(Reflect, Clone, Component, Debug, ActionBuilder)]
+#[reflect(AiComponent)]
+pub(crate) struct Sleep {
+    pub until: f32,
+    pub per_second: f32,
+    /// Walking speed toward the rest spot
+    pub speed: f32,
+}
+
+/// The closest rest spot in range, the path to it is searched by the [`MoveTo`]
+/// toward it
+fn nearest_rest_spot(position: Vec2, rest_spots: &Query<&GlobalTransform, With<RestSpot>>) -> Option<Vec2> {
+    rest_spots
+        .iter()
+        .map(|transform| transform.translation().truncate())
+        .filter(|spot| spot.distance(position) <= REST_SPOT_RANGE)
+        .filter(|spot| (spot.y - position.y).abs() <= REST_SPOT_HEIGHT)
+        .min_by(|a, b| a.distance_squared(position).total_cmp(&b.distance_squared(position)))
+}
+
+/// Walks to a rest spot, or stays where the actor is when there is none around,
+/// then sleeps until rested. Waking up early ([`Disturbed`]) fails the action.
+pub(crate) fn sleep_action_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut actors: Query<
+        (&mut Fatigue, &GlobalTransform, Option<&MoveTo>, Has<Asleep>, Has<Disturbed>, Has<PatrolSuspended>)
+    >,
+    rest_spots: Query<&GlobalTransform, With<RestSpot>>,
+    mut query: Query<(Entity, &Actor, &mut ActionState, &Sleep, &ActionSpan, Has<ResumesPatrol>)>
+) {
+    for (action, Actor(actor), mut state, sleep, span, resumes_patrol) in &mut query {
+        let _guard = span.span().enter();
+
+        let Ok((mut fatigue, transform, move_to, asleep, disturbed, patrol_suspended)) = actors.get_mut(*actor) else {
+            continue;
+        };
+        match *state {
+            ActionState::Requested => {
+                if disturbed {
+                    *state = ActionState::Failure;
+                    continue;
+                }
+                let position = transform.translation().truncate();
+                match nearest_rest_spot(position, &rest_spots) {
+                    Some(spot) => {
+                        debug!("Going to bed at {}", spot);
+                        commands.entity(*actor).insert(MoveTo::new(spot, sleep.speed));
+                    }
+                    None => debug!("No rest spot around, sleeping here"),
+                }
+                start_action(&mut commands, *actor, action, patrol_suspended);
+                *state = ActionState::Executing;
+            }
+            ActionState::Executing => {
+                if disturbed {
+                    debug!("Woken up before being rested");
+                    stop_action(&mut commands, *actor, action, resumes_patrol);
+                    *state = ActionState::Failure;
+                    continue;
+                }
+                if asleep {
+                    trace!("Sleeping...");
+                    fatigue.fatigue -=
+                        sleep.per_second * ((time.delta().as_micros() as f32) / 1_000_000.0);
+                    if fatigue.fatigue <= sleep.until {
+                        debug!("Done sleeping");
+                        commands.entity(*actor).remove::<Asleep>();
+                        stop_action(&mut commands, *actor, action, resumes_patrol);
+                        *state = ActionState::Success;
+                    }
+                    continue;
+                }
+                match move_to.map(|move_to| move_to.status) {
+                    Some(MoveStatus::Moving) => trace!("Walking to the rest spot..."),
+                    // Also when the path search finds no way there
+                    Some(MoveStatus::Blocked) => {
+                        debug!("Could not reach the rest spot");
+                        stop_action(&mut commands, *actor, action, resumes_patrol);
+                        *state = ActionState::Failure;
+                    }
+                    Some(MoveStatus::Arrived) | None => {
+                        debug!("Lying down");
+                        commands.entity(*actor).remove::<MoveTo>().insert(Asleep);
+                    }
+                }
+            }
+            ActionState::Cancelled => {
+                debug!("Action was cancelled. Considering this a failure.");
+                commands.entity(*actor).remove::<Asleep>();
+                stop_action(&mut commands, *actor, action, resumes_patrol);
+                *state = ActionState::Failure;
+            }
+            _ => {}
+        }
+    }
+}
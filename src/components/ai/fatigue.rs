@@ -0,0 +1,140 @@
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::prelude::*;
+use bevy_rapier2d::{ dynamics::Velocity, plugin::RapierContext };
+use input_manager::action_state::ActionState;
+
+use crate::{
+    components::{ facing::Facing, health::DamageEvent, interactions::InteractionSensor },
+    entities::Player,
+    plugins::input::Action,
+};
+
+/// Like [`Thirst`](super::thirst::Thirst), a need rising over time, and
+/// faster while moving. It only goes down by sleeping.
+#[derive(Component, Debug, Reflect)]
+pub(crate) struct Fatigue {
+    pub per_second: f32,
+    /// Extra fatigue for each pixel travelled
+    pub per_distance: f32,
+    pub fatigue: f32,
+}
+
+impl Fatigue {
+    pub fn new(fatigue: f32, per_second: f32, per_distance: f32) -> Self {
+        Self { fatigue, per_second, per_distance }
+    }
+}
+
+/// An actor lying down, recovering from [`Fatigue`], see [`lie_down`]
+#[derive(Component, Debug, Default)]
+pub(crate) struct Asleep;
+
+/// An actor that was woken up and can't go back to sleep until the timer ends
+#[derive(Component, Debug)]
+pub(crate) struct Disturbed(pub Timer);
+
+impl Default for Disturbed {
+    fn default() -> Self {
+        Self(Timer::from_seconds(15.0, TimerMode::Once))
+    }
+}
+
+pub(crate) fn fatigue_system(
+    time: Res<Time>,
+    mut fatigues: Query<(&mut Fatigue, Option<&Velocity>), Without<Asleep>>
+) {
+    let delta = (time.delta().as_micros() as f32) / 1_000_000.0;
+    for (mut fatigue, velocity) in &mut fatigues {
+        let distance = velocity.map_or(0.0, |velocity| velocity.linvel.length() * delta);
+        fatigue.fatigue += fatigue.per_second * delta + fatigue.per_distance * distance;
+        if fatigue.fatigue >= 100.0 {
+            debug!("Fatigue >= {}", fatigue.fatigue);
+            fatigue.fatigue = 100.0;
+        }
+
+        trace!("Fatigue: {}", fatigue.fatigue);
+    }
+}
+
+/// Wakes up sleeping actors when they get hurt
+pub(crate) fn wake_on_damage(
+    mut commands: Commands,
+    mut damage_events: EventReader<DamageEvent>,
+    sleepers: Query<(), With<Asleep>>
+) {
+    for event in damage_events.read() {
+        if sleepers.contains(event.target) {
+            debug!("{:?} was woken up by damage", event.target);
+            commands.entity(event.target).remove::<Asleep>().insert(Disturbed::default());
+        }
+    }
+}
+
+/// Wakes up the sleeping actor closest to a player pressing [`Action::Interact`].
+///
+/// Any sleeper in reach of the interaction sensor can be woken, not only the
+/// [`Interactive`](crate::components::interactions::Interactive) ones.
+pub(crate) fn wake_on_interaction(
+    mut commands: Commands,
+    sensors: Query<(Entity, &Parent), With<InteractionSensor>>,
+    players: Query<(&ActionState<Action>, &GlobalTransform), With<Player>>,
+    sleepers: Query<&GlobalTransform, With<Asleep>>,
+    rapier_context: Res<RapierContext>
+) {
+    for (sensor, parent) in &sensors {
+        let Ok((action_state, player_transform)) = players.get(**parent) else {
+            continue;
+        };
+        if !action_state.just_pressed(&Action::Interact) {
+            continue;
+        }
+        let position = player_transform.translation().truncate();
+        let closest = rapier_context
+            .intersection_pairs_with(sensor)
+            .filter(|(_, _, intersecting)| *intersecting)
+            .map(|(collider1, collider2, _)| if collider1 == sensor { collider2 } else { collider1 })
+            .filter_map(|entity| {
+                let transform = sleepers.get(entity).ok()?;
+                Some((entity, transform.translation().truncate().distance_squared(position)))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+        let Some((entity, _)) = closest else {
+            continue;
+        };
+        debug!("{:?} was woken up by the player", entity);
+        commands.entity(entity).remove::<Asleep>().insert(Disturbed::default());
+    }
+}
+
+/// Lays the sleeping actors down, their head away from where they were facing
+pub(crate) fn lie_down(mut query: Query<(&mut Transform, Option<&Facing>), Added<Asleep>>) {
+    for (mut transform, facing) in &mut query {
+        let side = facing.map_or(1.0, |facing| facing.0.x.signum());
+        transform.rotation = Quat::from_rotation_z(side * FRAC_PI_2);
+    }
+}
+
+/// Stands the actors back up once awake
+pub(crate) fn get_up(
+    mut woken: RemovedComponents<Asleep>,
+    mut query: Query<&mut Transform, Without<Asleep>>
+) {
+    for entity in woken.read() {
+        if let Ok(mut transform) = query.get_mut(entity) {
+            transform.rotation = Quat::IDENTITY;
+        }
+    }
+}
+
+pub(crate) fn calm_down(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Disturbed)>
+) {
+    for (entity, mut disturbed) in &mut query {
+        if disturbed.0.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Disturbed>();
+        }
+    }
+}
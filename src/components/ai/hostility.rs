@@ -0,0 +1,51 @@
+use bevy::prelude::*;
+
+use crate::components::{ ai::Brain, health::DamageEvent };
+
+/// Seconds an actor stays provoked after the last hit it took
+const PROVOKED_DURATION: f32 = 20.0;
+
+/// An aggressive actor fighting back whoever hurt it, until it calms down
+#[derive(Component, Debug)]
+pub(crate) struct Provoked {
+    pub by: Entity,
+    pub timer: Timer,
+}
+
+impl Provoked {
+    pub fn new(by: Entity) -> Self {
+        Self { by, timer: Timer::from_seconds(PROVOKED_DURATION, TimerMode::Once) }
+    }
+}
+
+/// Provokes the [`Brain::Aggressive`] actors getting hurt, or keeps them
+/// provoked
+pub(crate) fn provoke_on_damage(
+    mut commands: Commands,
+    mut damage_events: EventReader<DamageEvent>,
+    brains: Query<&Brain>
+) {
+    for event in damage_events.read() {
+        let Some(source) = event.source.filter(|source| *source != event.target) else {
+            continue;
+        };
+        if brains.get(event.target).is_ok_and(|brain| *brain == Brain::Aggressive) {
+            debug!("{:?} was provoked by {:?}", event.target, source);
+            commands.entity(event.target).insert(Provoked::new(source));
+        }
+    }
+}
+
+/// Calms down the actors after a while, or once whoever provoked them is gone
+pub(crate) fn forget_provocations(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Provoked)>,
+    targets: Query<(), With<GlobalTransform>>
+) {
+    for (entity, mut provoked) in &mut query {
+        if provoked.timer.tick(time.delta()).finished() || !targets.contains(provoked.by) {
+            commands.entity(entity).remove::<Provoked>();
+        }
+    }
+}
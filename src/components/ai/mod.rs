@@ -3,50 +3,225 @@
 // Not to be confused by an actual LLM!
 
 pub(crate) mod actions;
+pub(crate) mod movement;
+pub(crate) mod prediction;
 pub(crate) mod scorers;
+pub(crate) mod threats;
 
 // Components
+pub(crate) mod fatigue;
+pub(crate) mod hostility;
 pub(crate) mod thirst;
 
-use bevy::prelude::*;
-
-use big_brain::prelude::{ FirstToScore, Thinker };
-
-use crate::components::ai::{ actions::drink::Drink, scorers::thirsty::Thirsty, thirst::Thirst };
-
-// Now that we have all that defined, it's time to add a Thinker to an entity!
-// The Thinker is the actual "brain" behind all the AI. Every entity you want
-// to have AI behavior should have one *or more* Thinkers attached to it.
-pub(crate) fn setup(mut cmd: Commands) {
-    // Create the entity and throw the Thirst component in there. Nothing special here.
-    // Neutral AI Brain
-    cmd.spawn((
-        Thirst::new(75.0, 1.6),
-        Thinker::build()
-            .label("NeutralAIBrain")
-            .picker(FirstToScore { threshold: 0.8 })
-            // Technically these are supposed to be ActionBuilders and
-            // ScorerBuilders, but our Clone impls simplify our code here.
-            .when(Thirsty, Drink {
-                until: 1.0,
-                per_second: 5.0,
-            }),
-        // ⚠️ TODO:
-        // When damaged by the player, become hostile.
-    ));
-
-    // AggressiveAIBrain
-    cmd.spawn((
-        Thirst::new(75.0, 1.6),
-        Thinker::build()
-            .label("AggressiveAIBrain")
-            .picker(FirstToScore { threshold: 0.8 })
-            .when(Thirsty, Drink {
-                until: 1.0,
-                per_second: 5.0,
-            }),
-        // ⚠️ TODO:
-        // When player is in line of sight and at the proper
-        // aggro radius, engage in combat.
-    ));
+use bevy::{ prelude::*, reflect::FromType };
+use bevy_ecs_ldtk::{ ldtk::ldtk_fields::LdtkFields, EntityInstance };
+use big_brain::{ prelude::{ FirstToScore, Thinker, ThinkerBuilder }, BigBrainPlugin, BigBrainSet };
+
+use crate::{
+    components::ai::{
+        actions::{
+            attack::{ attack_action_system, Attack },
+            dodge::{ dodge_action_system, Dodge },
+            drink::{ drink_action_system, Drink },
+            sleep::{ sleep_action_system, Sleep },
+        },
+        fatigue::{
+            calm_down,
+            fatigue_system,
+            get_up,
+            lie_down,
+            wake_on_damage,
+            wake_on_interaction,
+            Fatigue,
+        },
+        hostility::{ forget_provocations, provoke_on_damage },
+        movement::{ move_to, request_paths, stop_moving, MoveTo },
+        prediction::{ record_player_history, PlayerHistory },
+        scorers::{
+            hostile::{ hostile_scorer_system, Hostile },
+            thirsty::{ thirsty_scorer_system, Thirsty },
+            threatened::{ threatened_scorer_system, Threatened },
+            tired::{ tired_scorer_system, Tired },
+        },
+        thirst::{ thirst_system, Thirst },
+    },
+    entities::{ cat::Cat, dog::Dog, enemy::Enemy, kade::Kade, npc::Npc },
+    plugins::{ dialogueview::not_in_dialogue, gamestate::GameState },
+};
+
+/// Type data marking the scorers, actions and states of the AIs, which tools
+/// like the AI debugger name through the type registry. Reflect them with
+/// `#[reflect(AiComponent)]`, and register their type.
+#[derive(Clone)]
+pub(crate) struct ReflectAiComponent;
+
+impl<T> FromType<T> for ReflectAiComponent {
+    fn from_type() -> Self {
+        Self
+    }
+}
+
+/// The kind of AI driving an actor, from the LDtk `brain` field
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+pub(crate) enum Brain {
+    Neutral,
+    Aggressive,
+}
+
+impl Brain {
+    fn from_field(value: &str) -> Option<Self> {
+        match value {
+            "Neutral" => Some(Self::Neutral),
+            "Aggressive" => Some(Self::Aggressive),
+            _ => None,
+        }
+    }
+
+    // The Thinker is the actual "brain" behind all the AI. Every entity you want
+    // to have AI behavior should have one *or more* Thinkers attached to it.
+    pub fn thinker(self) -> ThinkerBuilder {
+        match self {
+            Self::Neutral =>
+                Thinker::build()
+                    .label("NeutralAIBrain")
+                    .picker(FirstToScore { threshold: 0.8 })
+                    // Technically these are supposed to be ActionBuilders and
+                    // ScorerBuilders, but our Clone impls simplify our code here.
+                    .when(Threatened { radius: 24.0, horizon: 1.0 }, Dodge {
+                        radius: 24.0,
+                        horizon: 1.0,
+                        distance: 32.0,
+                        speed: 60.0,
+                    })
+                    .when(Thirsty, Drink {
+                        until: 1.0,
+                        per_second: 5.0,
+                        speed: 30.0,
+                    })
+                    .when(Tired, Sleep {
+                        until: 5.0,
+                        per_second: 10.0,
+                        speed: 25.0,
+                    }),
+            // Fights back whoever hurts it, out of the way of their attacks,
+            // before tending to its needs
+            Self::Aggressive =>
+                Thinker::build()
+                    .label("AggressiveAIBrain")
+                    .picker(FirstToScore { threshold: 0.8 })
+                    .when(Threatened { radius: 24.0, horizon: 0.5 }, Dodge {
+                        radius: 24.0,
+                        horizon: 0.5,
+                        distance: 24.0,
+                        speed: 80.0,
+                    })
+                    .when(Hostile, Attack {
+                        speed: 50.0,
+                        reach: 16.0,
+                        damage: 5,
+                        cooldown: 1.0,
+                    })
+                    .when(Thirsty, Drink {
+                        until: 1.0,
+                        per_second: 5.0,
+                        speed: 30.0,
+                    })
+                    .when(Tired, Sleep {
+                        until: 5.0,
+                        per_second: 10.0,
+                        speed: 25.0,
+                    }),
+        }
+    }
+}
+
+/// Attaches a Thinker, and the needs it works with, to the actors having a
+/// `brain` field. Enemies are aggressive by default, and have no needs: their
+/// state machine is suspended while an action runs.
+pub(crate) fn attach_brains(
+    mut commands: Commands,
+    query: Query<
+        (Entity, &EntityInstance, Has<Enemy>),
+        (
+            Added<EntityInstance>,
+            Or<(With<Npc>, With<Dog>, With<Cat>, With<Kade>, With<Enemy>)>,
+        )
+    >
+) {
+    for (entity, entity_instance, is_enemy) in &query {
+        let field = entity_instance
+            .get_enum_field("brain")
+            .or_else(|_| entity_instance.get_string_field("brain"));
+        let brain = match field {
+            Ok(field) => {
+                let Some(brain) = Brain::from_field(field) else {
+                    warn!("Unknown brain {:?} for {}", field, entity_instance.identifier);
+                    continue;
+                };
+                brain
+            }
+            Err(_) if is_enemy => Brain::Aggressive,
+            Err(_) => {
+                continue;
+            }
+        };
+        debug!("{} {:?} gets a {:?} brain", entity_instance.identifier, entity, brain);
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.insert((brain, brain.thinker()));
+        if !is_enemy {
+            entity_commands.insert((Thirst::new(75.0, 1.6), Fatigue::new(20.0, 0.4, 0.01)));
+        }
+    }
+}
+
+pub(crate) fn plugin(app: &mut App) {
+    app.register_type::<Brain>()
+        .register_type::<Thirst>()
+        .register_type::<Fatigue>()
+        .register_type::<MoveTo>()
+        .register_type::<Attack>()
+        .register_type::<Dodge>()
+        .register_type::<Drink>()
+        .register_type::<Sleep>()
+        .register_type::<Hostile>()
+        .register_type::<Thirsty>()
+        .register_type::<Threatened>()
+        .register_type::<Tired>()
+        .init_resource::<PlayerHistory>()
+        .add_plugins(BigBrainPlugin::new(PreUpdate))
+        .add_systems(
+            PreUpdate,
+            (
+                (
+                    attack_action_system,
+                    dodge_action_system,
+                    drink_action_system,
+                    sleep_action_system,
+                ).in_set(BigBrainSet::Actions),
+                (
+                    hostile_scorer_system,
+                    thirsty_scorer_system,
+                    threatened_scorer_system,
+                    tired_scorer_system,
+                ).in_set(BigBrainSet::Scorers),
+            )
+        )
+        .add_systems(
+            Update,
+            (
+                attach_brains,
+                record_player_history,
+                thirst_system,
+                fatigue_system,
+                (
+                    wake_on_damage,
+                    wake_on_interaction.run_if(not_in_dialogue),
+                    calm_down,
+                    lie_down,
+                    get_up,
+                ),
+                (provoke_on_damage, forget_provocations),
+                (request_paths, move_to, stop_moving).chain(),
+            ).run_if(in_state(GameState::Playing))
+        );
 }
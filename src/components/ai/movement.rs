@@ -0,0 +1,191 @@
+use bevy::prelude::*;
+use bevy_rapier2d::{ dynamics::Velocity, plugin::RapierContext };
+
+use crate::{
+    components::{ predefinedpath::PatrolSuspended, shapeshift::Shapeshifted },
+    plugins::pathfinding::{ self, graph::EdgeKind, NavStep, PathRequest, PathResult, PathSearch },
+};
+
+/// How far ahead of a moving actor walls are looked for
+const LOOKAHEAD: f32 = 12.0;
+/// Vertical distance at which a step that isn't walked is reached, half a cell
+const STEP_HEIGHT: f32 = 8.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+pub(crate) enum MoveStatus {
+    Moving,
+    Arrived,
+    /// Level geometry is in the way
+    Blocked,
+}
+
+/// Movement goal of an AI actor, walked toward along a path from the
+/// navigation graph.
+///
+/// Actors have kinematic bodies without gravity: they walk horizontally, and
+/// move straight to the end of the jump, drop, climb and swim steps.
+///
+/// Actions insert it on their actor and watch its [`MoveStatus`], removing it
+/// stops the actor.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Reflect)]
+pub(crate) struct MoveTo {
+    pub target: Vec2,
+    pub speed: f32,
+    /// Horizontal distance at which the target, or a step of the path, is
+    /// reached
+    pub tolerance: f32,
+    pub status: MoveStatus,
+}
+
+impl MoveTo {
+    pub fn new(target: Vec2, speed: f32) -> Self {
+        Self {
+            target,
+            speed,
+            tolerance: 4.0,
+            status: MoveStatus::Moving,
+        }
+    }
+
+    pub fn with_tolerance(mut self, tolerance: f32) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+}
+
+/// An actor moved around by one of its actions, the other behaviours leave it
+/// be meanwhile
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub(crate) struct Busy;
+
+/// Marks an action that suspended the patrol of its actor, to resume it once
+/// the action is over
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub(crate) struct ResumesPatrol;
+
+/// Makes the actor of `action` [`Busy`], and stops its patrol unless something
+/// else already did
+pub(crate) fn start_action(commands: &mut Commands, actor: Entity, action: Entity, patrol_suspended: bool) {
+    commands.entity(actor).insert(Busy);
+    if patrol_suspended {
+        return;
+    }
+    commands.entity(actor).insert((PatrolSuspended, Velocity::zero()));
+    commands.entity(action).insert(ResumesPatrol);
+}
+
+/// Stops the actor of `action` moving, and resumes its patrol if the action
+/// suspended it
+pub(crate) fn stop_action(commands: &mut Commands, actor: Entity, action: Entity, resumes_patrol: bool) {
+    commands.entity(actor).remove::<(MoveTo, Busy)>();
+    if resumes_patrol {
+        commands.entity(actor).remove::<PatrolSuspended>();
+        commands.entity(action).remove::<ResumesPatrol>();
+    }
+}
+
+/// Asks for a path to new movement goals
+pub(crate) fn request_paths(
+    mut commands: Commands,
+    query: Query<(Entity, &GlobalTransform, &MoveTo, Option<&PathResult>), Changed<MoveTo>>
+) {
+    for (entity, transform, move_to, result) in &query {
+        if move_to.status != MoveStatus::Moving {
+            continue;
+        }
+        if result.is_some_and(|result| result.to == move_to.target) {
+            continue;
+        }
+        commands.entity(entity).insert(PathRequest {
+            from: transform.translation().truncate(),
+            to: move_to.target,
+        });
+    }
+}
+
+pub(crate) fn move_to(
+    mut query: Query<
+        (&GlobalTransform, &mut Velocity, &mut MoveTo, Option<&mut PathResult>),
+        Without<Shapeshifted>
+    >,
+    rapier_context: Res<RapierContext>
+) {
+    for (transform, mut velocity, mut move_to, result) in &mut query {
+        if move_to.status != MoveStatus::Moving {
+            continue;
+        }
+        let position = transform.translation().truncate();
+        // Head for the next step of the path once found, straight to the
+        // target until then
+        let mut waypoint = move_to.target;
+        let mut kind = EdgeKind::Walk;
+        // Whether the path still goes up or down
+        let mut vertical = false;
+        if let Some(mut result) = result.filter(|result| result.to == move_to.target) {
+            let Some(steps) = &mut result.path else {
+                debug!("No path to {}", move_to.target);
+                move_to.status = MoveStatus::Blocked;
+                velocity.linvel = Vec2::ZERO;
+                continue;
+            };
+            let reached = steps
+                .iter()
+                .take_while(|step| step_reached(step, position, move_to.tolerance))
+                .count();
+            steps.drain(..reached);
+            if let Some(step) = steps.first() {
+                trace!("{:?} to {}", step.kind, step.position);
+                waypoint = step.position;
+                kind = step.kind;
+            }
+            vertical = steps.iter().any(|step| step.kind != EdgeKind::Walk);
+        }
+        if !vertical && (move_to.target.x - position.x).abs() <= move_to.tolerance {
+            move_to.status = MoveStatus::Arrived;
+            velocity.linvel = Vec2::ZERO;
+            continue;
+        }
+        let direction = match kind {
+            EdgeKind::Walk => Vec2::new((waypoint.x - position.x).signum(), 0.0),
+            _ => (waypoint - position).normalize_or_zero(),
+        };
+        if direction.length_squared() <= f32::EPSILON {
+            continue;
+        }
+        let ahead = position + direction * LOOKAHEAD;
+        if !pathfinding::walkable(&rapier_context, position, ahead) {
+            debug!("Blocked on the way to {}", move_to.target);
+            move_to.status = MoveStatus::Blocked;
+            velocity.linvel = Vec2::ZERO;
+            continue;
+        }
+        velocity.linvel = direction * move_to.speed;
+    }
+}
+
+/// Whether an actor at `position` is done with `step`
+fn step_reached(step: &NavStep, position: Vec2, tolerance: f32) -> bool {
+    match step.kind {
+        EdgeKind::Walk => (step.position.x - position.x).abs() <= tolerance,
+        _ => {
+            let offset = (step.position - position).abs();
+            offset.x <= tolerance && offset.y <= STEP_HEIGHT
+        }
+    }
+}
+
+/// Stops the actors whose movement goal was removed, and forgets their path
+pub(crate) fn stop_moving(
+    mut commands: Commands,
+    mut removed: RemovedComponents<MoveTo>,
+    mut velocities: Query<&mut Velocity, Without<Shapeshifted>>
+) {
+    for entity in removed.read() {
+        if let Ok(mut velocity) = velocities.get_mut(entity) {
+            velocity.linvel = Vec2::ZERO;
+        }
+        if let Some(mut entity_commands) = commands.get_entity(entity) {
+            entity_commands.remove::<(PathRequest, PathSearch, PathResult)>();
+        }
+    }
+}
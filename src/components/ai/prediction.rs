@@ -0,0 +1,233 @@
+//! Recent history of the player, for the AI to anticipate their moves.
+//!
+//! [`PlayerHistory`] keeps the last seconds of the player's actions and
+//! velocity. [`PlayerPrediction`] extrapolates their trajectory from it under
+//! the current gravity, for scorers and actions to aim ahead of the player or
+//! guess where they land.
+
+use std::collections::VecDeque;
+
+use bevy::{ ecs::system::SystemParam, prelude::* };
+use bevy_rapier2d::{
+    dynamics::{ GravityScale, Velocity },
+    geometry::Collider,
+    pipeline::QueryFilter,
+    plugin::{ RapierConfiguration, RapierContext },
+};
+use input_manager::action_state::ActionState;
+
+use crate::{ entities::Player, plugins::input::Action };
+
+/// Time step of the simulated trajectories
+const TIME_STEP: f32 = 1.0 / 30.0;
+
+#[derive(Clone, Debug)]
+pub(crate) struct PlayerSample {
+    /// Elapsed seconds
+    pub time: f32,
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub actions: ActionState<Action>,
+}
+
+/// Rolling buffer of the latest [`PlayerSample`]s
+#[derive(Resource, Debug)]
+pub(crate) struct PlayerHistory {
+    player: Option<Entity>,
+    samples: VecDeque<PlayerSample>,
+    /// Seconds of history kept
+    pub duration: f32,
+}
+
+impl Default for PlayerHistory {
+    fn default() -> Self {
+        Self {
+            player: None,
+            samples: VecDeque::new(),
+            duration: 2.0,
+        }
+    }
+}
+
+impl PlayerHistory {
+    pub fn player(&self) -> Option<Entity> {
+        self.player
+    }
+
+    pub fn latest(&self) -> Option<&PlayerSample> {
+        self.samples.back()
+    }
+
+    /// Samples of the last `seconds`, oldest first
+    pub fn since(&self, seconds: f32) -> impl Iterator<Item = &PlayerSample> {
+        let now = self.latest().map_or(0.0, |sample| sample.time);
+        self.samples.iter().filter(move |sample| now - sample.time <= seconds)
+    }
+
+    /// Whether `action` was pressed during the last `seconds`
+    pub fn just_pressed_within(&self, action: &Action, seconds: f32) -> bool {
+        self.since(seconds).any(|sample| sample.actions.just_pressed(action))
+    }
+}
+
+pub(crate) fn record_player_history(
+    time: Res<Time>,
+    mut history: ResMut<PlayerHistory>,
+    players: Query<(Entity, &GlobalTransform, &Velocity, &ActionState<Action>), With<Player>>
+) {
+    let Ok((player, transform, velocity, actions)) = players.get_single() else {
+        if history.player.is_some() {
+            history.player = None;
+            history.samples.clear();
+        }
+        return;
+    };
+    let now = time.elapsed_seconds();
+    history.player = Some(player);
+    history.samples.push_back(PlayerSample {
+        time: now,
+        position: transform.translation().truncate(),
+        velocity: velocity.linvel,
+        actions: actions.clone(),
+    });
+    let duration = history.duration;
+    while history.samples.front().is_some_and(|sample| now - sample.time > duration) {
+        history.samples.pop_front();
+    }
+}
+
+/// Extrapolated motion of the player
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Trajectory {
+    /// Positions at each time step, from the current one
+    pub points: Vec<Vec2>,
+    /// Where the player first lands, if they do
+    pub landing: Option<Vec2>,
+}
+
+impl Trajectory {
+    /// Where the player lands, unless they are already standing
+    pub fn airborne_landing(&self) -> Option<Vec2> {
+        let start = *self.points.first()?;
+        self.landing.filter(|landing| landing.distance(start) > 1.0)
+    }
+}
+
+/// Predicts the moves of the player from the [`PlayerHistory`]
+#[derive(SystemParam)]
+pub(crate) struct PlayerPrediction<'w, 's> {
+    history: Res<'w, PlayerHistory>,
+    rapier_config: Res<'w, RapierConfiguration>,
+    rapier_context: Res<'w, RapierContext>,
+    players: Query<'w, 's, (&'static Collider, Option<&'static GravityScale>), With<Player>>,
+}
+
+impl PlayerPrediction<'_, '_> {
+    pub fn is_player(&self, entity: Entity) -> bool {
+        self.history.player() == Some(entity)
+    }
+
+    /// Whether the player jumped during the last `seconds`
+    pub fn jumped_within(&self, seconds: f32) -> bool {
+        self.history.just_pressed_within(&Action::Jump, seconds)
+    }
+
+    /// Distance the player can move along `direction` before hitting a wall
+    fn free_distance(&self, position: Vec2, direction: Vec2, distance: f32, half_extent: f32) -> f32 {
+        self.rapier_context
+            .cast_ray(
+                position,
+                direction,
+                distance + half_extent,
+                true,
+                QueryFilter::only_fixed().exclude_sensors()
+            )
+            .map_or(distance, |(_, toi)| (toi - half_extent).max(0.0))
+    }
+
+    /// Motion of the player over the next `seconds`, keeping their momentum
+    /// under the current gravity and stopped by walls
+    pub fn trajectory(&self, seconds: f32) -> Option<Trajectory> {
+        let sample = self.history.latest()?;
+        let (collider, gravity_scale) = self.players.get_single().ok()?;
+        let half_extents = collider
+            .as_cuboid()
+            .map_or(Vec2::splat(8.0), |cuboid| cuboid.half_extents());
+        let gravity = self.rapier_config.gravity * gravity_scale.map_or(1.0, |scale| scale.0);
+
+        let mut position = sample.position;
+        let mut velocity = sample.velocity;
+        let mut trajectory = Trajectory { points: vec![position], landing: None };
+        for _ in 0..(seconds / TIME_STEP).ceil() as usize {
+            velocity += gravity * TIME_STEP;
+            // Each axis on its own, so the player slides along walls and floors
+            let dx = velocity.x * TIME_STEP;
+            let free = self.free_distance(position, Vec2::X * dx.signum(), dx.abs(), half_extents.x);
+            if free < dx.abs() {
+                velocity.x = 0.0;
+            }
+            position.x += free * dx.signum();
+            let dy = velocity.y * TIME_STEP;
+            let free = self.free_distance(position, Vec2::Y * dy.signum(), dy.abs(), half_extents.y);
+            if free < dy.abs() {
+                if velocity.y < 0.0 && trajectory.landing.is_none() {
+                    trajectory.landing = Some(position - Vec2::Y * free);
+                }
+                velocity.y = 0.0;
+            }
+            position.y += free * dy.signum();
+            trajectory.points.push(position);
+        }
+        Some(trajectory)
+    }
+
+    /// Where the player will be in `seconds`
+    pub fn position_in(&self, seconds: f32) -> Option<Vec2> {
+        self.trajectory(seconds).and_then(|trajectory| trajectory.points.last().copied())
+    }
+
+    /// Where the player lands, if they do within `horizon` seconds
+    pub fn landing_spot(&self, horizon: f32) -> Option<Vec2> {
+        self.trajectory(horizon).and_then(|trajectory| trajectory.landing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(jumps: &[f32]) -> PlayerHistory {
+        let mut history = PlayerHistory::default();
+        for time in [0.0, 0.5, 1.0, 1.5] {
+            let mut actions = ActionState::<Action>::default();
+            if jumps.contains(&time) {
+                actions.press(&Action::Jump);
+            }
+            history.samples.push_back(PlayerSample {
+                time,
+                position: Vec2::ZERO,
+                velocity: Vec2::ZERO,
+                actions,
+            });
+        }
+        history
+    }
+
+    #[test]
+    fn since_keeps_the_latest_samples() {
+        let history = history(&[]);
+        let times = |seconds| history.since(seconds).map(|sample| sample.time).collect::<Vec<_>>();
+        assert_eq!(times(0.6), vec![1.0, 1.5]);
+        assert_eq!(times(0.0), vec![1.5]);
+        assert_eq!(times(10.0), vec![0.0, 0.5, 1.0, 1.5]);
+        assert_eq!(PlayerHistory::default().since(1.0).count(), 0);
+    }
+
+    #[test]
+    fn just_pressed_within_looks_back_in_time() {
+        let history = history(&[0.5]);
+        assert!(history.just_pressed_within(&Action::Jump, 1.0));
+        assert!(!history.just_pressed_within(&Action::Jump, 0.9));
+        assert!(!history.just_pressed_within(&Action::Interact, 2.0));
+    }
+}
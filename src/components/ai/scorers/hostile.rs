@@ -0,0 +1,18 @@
+use bevy::prelude::*;
+use big_brain::prelude::*;
+
+use crate::components::ai::{ hostility::Provoked, ReflectAiComponent };
+
+#[derive(Reflect, Clone, Component, Debug, ScorerBuilder)]
+#[reflect(AiComponent)]
+pub(crate) struct Hostile;
+
+/// All or nothing: a provoked actor drops whatever it's doing
+pub(crate) fn hostile_scorer_system(
+    provoked: Query<(), With<Provoked>>,
+    mut query: Query<(&Actor, &mut Score), With<Hostile>>
+) {
+    for (Actor(actor), mut score) in &mut query {
+        score.set(if provoked.contains(*actor) { 1.0 } else { 0.0 });
+    }
+}
@@ -8,4 +8,7 @@
 // Again, in most cases, you can use the `ScorerBuilder` derive macro to make your
 // Scorer Component act as a ScorerBuilder. You need it to implement Clone and Debug.
 
+pub(crate) mod hostile;
 pub(crate) mod thirsty;
+pub(crate) mod threatened;
+pub(crate) mod tired;
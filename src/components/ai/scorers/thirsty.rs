@@ -1,9 +1,10 @@
 use bevy::prelude::*;
 use big_brain::prelude::*;
 
-use crate::components::ai::thirst::Thirst;
+use crate::components::ai::{ thirst::Thirst, ReflectAiComponent };
 
-#[derive(Clone, Component, Debug, ScorerBuilder)]
+#[derive(Reflect, Clone, Component, Debug, ScorerBuilder)]
+#[reflect(AiComponent)]
 pub(crate) struct Thirsty;
 
 // Then, we have something called "Scorers".
@@ -0,0 +1,34 @@
+use bevy::prelude::*;
+use big_brain::prelude::*;
+
+use crate::components::ai::{ threats::Threats, ReflectAiComponent };
+
+/// An attack is about to hit the actor
+#[derive(Reflect, Clone, Component, Debug, ScorerBuilder)]
+#[reflect(AiComponent)]
+pub(crate) struct Threatened {
+    /// Distance from the actor threats count within
+    pub radius: f32,
+    /// Seconds ahead threats are anticipated
+    pub horizon: f32,
+}
+
+/// All or nothing: whether the player lands on the actor, or a projectile or a
+/// melee attack of theirs is about to hit it
+pub(crate) fn threatened_scorer_system(
+    threats: Threats,
+    actors: Query<&GlobalTransform>,
+    mut query: Query<(&Actor, &mut Score, &Threatened, &ScorerSpan)>
+) {
+    for (Actor(actor), mut score, threatened, span) in &mut query {
+        let Ok(transform) = actors.get(*actor) else {
+            continue;
+        };
+        let position = transform.translation().truncate();
+        let threat = threats.incoming(*actor, position, threatened.radius, threatened.horizon);
+        score.set(if threat.is_some() { 1.0 } else { 0.0 });
+        if let Some(threat) = threat {
+            span.span().in_scope(|| trace!("Threatened from {}", threat));
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use bevy::prelude::*;
+use big_brain::prelude::*;
+
+use crate::components::ai::{ fatigue::{ Disturbed, Fatigue }, ReflectAiComponent };
+
+#[derive(Reflect, Clone, Component, Debug, ScorerBuilder)]
+#[reflect(AiComponent)]
+pub(crate) struct Tired;
+
+pub(crate) fn tired_scorer_system(
+    fatigues: Query<(&Fatigue, Has<Disturbed>)>,
+    mut query: Query<(&Actor, &mut Score, &ScorerSpan), With<Tired>>
+) {
+    for (Actor(actor), mut score, span) in &mut query {
+        if let Ok((fatigue, disturbed)) = fatigues.get(*actor) {
+            // Someone who was just woken up stays awake for a while
+            if disturbed {
+                score.set(0.0);
+                continue;
+            }
+            score.set(fatigue.fatigue / 100.0);
+            if fatigue.fatigue >= 80.0 {
+                span.span().in_scope(|| {
+                    debug!("Fatigue above threshold! Score: {}", fatigue.fatigue / 100.0)
+                });
+            }
+        }
+    }
+}
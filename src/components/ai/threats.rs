@@ -0,0 +1,96 @@
+//! Incoming attacks, for the AI to get out of the way.
+//!
+//! [`Threats`] looks for whatever is about to hit an actor: the player landing
+//! on it, a projectile flying at it, or the player winding up a melee attack
+//! next to it.
+
+use bevy::{ ecs::system::SystemParam, prelude::* };
+use bevy_rapier2d::dynamics::Velocity;
+
+use super::prediction::PlayerPrediction;
+use crate::components::{
+    hitbox::{ AttackPhase, MeleeAttack },
+    projectile::{ Pooled, Projectile },
+};
+
+/// Where a projectile from `origin` at `velocity` passes the closest to
+/// `position`, if it flies toward it and gets there within `horizon` seconds
+fn closest_approach(origin: Vec2, velocity: Vec2, position: Vec2, horizon: f32) -> Option<Vec2> {
+    let speed_squared = velocity.length_squared();
+    if speed_squared <= f32::EPSILON {
+        return None;
+    }
+    let time = (position - origin).dot(velocity) / speed_squared;
+    if !(0.0..=horizon).contains(&time) {
+        return None;
+    }
+    Some(origin + velocity * time)
+}
+
+#[derive(SystemParam)]
+pub(crate) struct Threats<'w, 's> {
+    prediction: PlayerPrediction<'w, 's>,
+    projectiles: Query<
+        'w,
+        's,
+        (&'static Projectile, &'static GlobalTransform, &'static Velocity),
+        Without<Pooled>
+    >,
+    attacks: Query<'w, 's, (Entity, &'static MeleeAttack, &'static GlobalTransform)>,
+}
+
+impl Threats<'_, '_> {
+    /// Where the attack about to hit `actor`, at `position`, comes from: the
+    /// spot the player lands on, a projectile passing within `radius` in the
+    /// next `horizon` seconds, or a player attacking from within `radius`
+    pub fn incoming(&self, actor: Entity, position: Vec2, radius: f32, horizon: f32) -> Option<Vec2> {
+        let landing = self.prediction
+            .trajectory(horizon)
+            .and_then(|trajectory| trajectory.airborne_landing())
+            .filter(|spot| (spot.x - position.x).abs() <= radius);
+        if landing.is_some() {
+            return landing;
+        }
+        let attacker = self.attacks
+            .iter()
+            .filter(|(attacker, attack, _)| {
+                self.prediction.is_player(*attacker) && attack.phase != AttackPhase::Recovery
+            })
+            .map(|(_, _, transform)| transform.translation().truncate())
+            .find(|attacker| attacker.distance(position) <= radius);
+        if attacker.is_some() {
+            return attacker;
+        }
+        self.projectiles
+            .iter()
+            .filter(|(projectile, _, _)| projectile.owner != actor)
+            .map(|(_, transform, velocity)| (transform.translation().truncate(), velocity.linvel))
+            .filter(|(origin, velocity)| {
+                closest_approach(*origin, *velocity, position, horizon).is_some_and(|closest| {
+                    closest.distance(position) <= radius
+                })
+            })
+            .map(|(origin, _)| origin)
+            .min_by(|a, b| a.distance_squared(position).total_cmp(&b.distance_squared(position)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn projectiles_flying_at_the_actor_approach_it() {
+        let closest = closest_approach(Vec2::ZERO, Vec2::new(100.0, 10.0), Vec2::new(50.0, 0.0), 1.0).unwrap();
+        assert!((closest.x - 49.5).abs() < 0.1 && (closest.y - 4.95).abs() < 0.1);
+    }
+
+    #[test]
+    fn projectiles_flying_away_or_too_slow_are_ignored() {
+        // Already past the actor
+        assert_eq!(closest_approach(Vec2::ZERO, Vec2::X * 100.0, Vec2::new(-50.0, 0.0), 1.0), None);
+        // Further than a second away
+        assert_eq!(closest_approach(Vec2::ZERO, Vec2::X * 100.0, Vec2::new(150.0, 0.0), 1.0), None);
+        assert_eq!(closest_approach(Vec2::ZERO, Vec2::ZERO, Vec2::new(10.0, 0.0), 1.0), None);
+    }
+}
@@ -1,13 +1,18 @@
 use bevy::prelude::*;
+use bevy_ecs_ldtk::{ ldtk::ldtk_fields::LdtkFields, EntityInstance };
+use bevy_yarnspinner::prelude::{ DialogueRunner, YarnValue };
+use rand::{ seq::IteratorRandom, Rng };
 
-// ⚠️ NOTE: Port to bevy_rand somehow.
-use rand::seq::SliceRandom;
-use rand::thread_rng;
+use super::interactions::InteractionSensor;
+use crate::{
+    entities::{ cat::Cat, dog::Dog },
+    plugins::{ entropy::{ EntropyAppExt, ForkedEntropy }, gamestate::GameState },
+};
 
 // 🐾
 
 // Animal types
-#[derive(Component, PartialEq, Eq)]
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
 pub enum AnimalType {
     Cat,
     Dog,
@@ -17,6 +22,12 @@ pub trait Animal: Component {
     // A static method that returns a static string reference, representing the species of the animal
     fn species() -> &'static str;
 
+    // The type the names of the species are drawn from
+    fn animal_type() -> AnimalType;
+
+    // Creates an animal of the species with the given name
+    fn new(name: String) -> Self;
+
     // A method that returns a reference to a String, representing the name of the animal
     fn name(&self) -> &String;
 
@@ -37,33 +48,98 @@ pub trait Animal: Component {
         // If no match is found, return None
         None
     }
+
+    // The pronoun used for the animal in dialogues
+    fn pronoun(&self) -> &'static str {
+        match self.gender() {
+            Some("male") => "he",
+            Some("female") => "she",
+            _ => "they",
+        }
+    }
 }
 
 // Function to generate an animal name based on the given animal type
-fn generate_animal_name(animal_type: AnimalType) -> String {
-    // Create a random number generator
-    let mut rng = thread_rng();
-
-    // Choose a random animal name from the ANIMAL_NAMES array
-    // The chosen element is a tuple containing the name, gender, and type of the animal
-    match ANIMAL_NAMES.choose(&mut rng) {
-        Some((name, _gender, name_type)) => {
-            // If the type of the chosen animal matches the given animal type
-            if *name_type == animal_type {
-                // Return the name of the animal as a string
-                name.to_string()
-            } else {
-                // If the types don't match, recursively call the function until a matching animal name is found
-                generate_animal_name(animal_type)
+fn generate_animal_name<R: Rng + ?Sized>(animal_type: AnimalType, rng: &mut R) -> String {
+    // Choose a random name among the ones of the given animal type, the same
+    // seed always giving the same names
+    ANIMAL_NAMES.iter()
+        .filter(|(_, _, name_type)| *name_type == animal_type)
+        .choose(rng)
+        .map_or_else(
+            // If there is no name for this type, return a default name
+            || "Default Animal Name".to_string(),
+            |(name, _gender, _type)| name.to_string()
+        )
+}
+
+// Names the animals spawned without one, from their LDtk `name` field or else
+// drawn from the seeded entropy
+pub(crate) fn name_animals<A: Animal>(
+    mut commands: Commands,
+    animals: Query<(Entity, &A, Option<&EntityInstance>), Added<A>>,
+    mut rng: ResMut<ForkedEntropy<AnimalType>>
+) {
+    for (entity, animal, entity_instance) in &animals {
+        let name = match entity_instance.and_then(|instance| instance.get_string_field("name").ok()) {
+            Some(name) if !name.is_empty() => name.clone(),
+            _ if !animal.name().is_empty() => animal.name().clone(),
+            _ => generate_animal_name(A::animal_type(), rng.as_mut()),
+        };
+        debug!("New {}: {}", A::species(), name);
+        commands
+            .entity(entity)
+            .insert((Name::new(format!("{} {}", A::species(), name)), A::new(name), A::animal_type()));
+    }
+}
+
+// Lets dialogues refer to the animal closest to the player, through the
+// `$animal_name`, `$animal_species` and `$animal_pronoun` variables, which
+// are emptied once no animal is the closest
+pub(crate) fn expose_animal_to_dialogue<A: Animal>(
+    sensors: Query<&InteractionSensor, Changed<InteractionSensor>>,
+    animals: Query<&A>,
+    other_animals: Query<(), (With<AnimalType>, Without<A>)>,
+    mut dialogue_runners: Query<&mut DialogueRunner>
+) {
+    for sensor in &sensors {
+        let values = match sensor.closest_entity {
+            Some(entity) if other_animals.contains(entity) => {
+                // Exposed by the system of its own species
+                continue;
+            }
+            Some(entity) =>
+                match animals.get(entity) {
+                    Ok(animal) => [animal.name().as_str(), A::species(), animal.pronoun()],
+                    Err(_) => ["", "", ""],
+                }
+            None => ["", "", ""],
+        };
+        for mut dialogue_runner in &mut dialogue_runners {
+            let storage = dialogue_runner.variable_storage_mut();
+            for (variable, value) in ["$animal_name", "$animal_species", "$animal_pronoun"]
+                .into_iter()
+                .zip(values) {
+                if let Err(error) = storage.set(variable.to_string(), YarnValue::from(value)) {
+                    warn!("Couldn't set {}: {}", variable, error);
+                }
             }
-        }
-        None => {
-            // If the ANIMAL_NAMES array is empty, return a default name
-            "Default Animal Name".to_string()
         }
     }
 }
 
+pub(crate) fn plugin(app: &mut App) {
+    app.init_forked_entropy::<AnimalType>().add_systems(
+        Update,
+        (
+            // One after the other, they draw from the same entropy
+            (name_animals::<Dog>, name_animals::<Cat>).chain(),
+            expose_animal_to_dialogue::<Dog>,
+            expose_animal_to_dialogue::<Cat>,
+        ).run_if(in_state(GameState::Playing))
+    );
+}
+
 #[rustfmt::skip]
 pub const ANIMAL_NAMES: &[(&str, &str, AnimalType)] = &[
     ("Malcolm", "male", AnimalType::Dog), ("Zoe", "female", AnimalType::Dog), ("Wash", "male", AnimalType::Dog),
@@ -125,3 +201,38 @@ pub const ANIMAL_NAMES: &[(&str, &str, AnimalType)] = &[
     ("Sarina", "female", AnimalType::Cat), ("Hugh", "male", AnimalType::Cat), ("Lore", "male", AnimalType::Cat),
     ("Elaurian", "male", AnimalType::Cat), // End of 🐈‍⬛
 ];
+
+#[cfg(test)]
+mod tests {
+    use bevy_rand::prelude::WyRand;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    fn names(animal_type: AnimalType, seed: u64) -> Vec<String> {
+        let mut rng = WyRand::seed_from_u64(seed);
+        (0..8).map(|_| generate_animal_name(animal_type, &mut rng)).collect()
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_names() {
+        assert_eq!(names(AnimalType::Dog, 1990), names(AnimalType::Dog, 1990));
+        assert_eq!(names(AnimalType::Cat, 1990), names(AnimalType::Cat, 1990));
+    }
+
+    #[test]
+    fn names_are_drawn_from_the_animal_type() {
+        for animal_type in [AnimalType::Dog, AnimalType::Cat] {
+            for name in names(animal_type, 2024) {
+                assert!(
+                    ANIMAL_NAMES.iter().any(|&(animal_name, _, name_type)| {
+                        animal_name == name && name_type == animal_type
+                    }),
+                    "{} isn't a {:?} name",
+                    name,
+                    animal_type
+                );
+            }
+        }
+    }
+}
@@ -2,6 +2,7 @@ use std::collections::HashSet;
 use bevy::prelude::*;
 use bevy_rapier2d::{ dynamics::GravityScale, pipeline::CollisionEvent };
 
+use super::status_effects::{ BaseGravityScale, StatusEffects };
 use crate::plugins::rapier_utils::reciprocal_collisions;
 
 // Attach this to any component to allow the player (or any climber entity) to climb up and
@@ -45,15 +46,20 @@ pub fn detect_climb_range(
 
 // Checks if a climber entity is climbing.
 // If it is, the gravity scale is set to 0.0, effectively ignoring gravity.
-// If the climber is not climbing, the gravity scale is set back to 1.0.
+// If the climber is not climbing, the gravity scale is set back to its base one, scaled by the status effects.
 pub fn ignore_gravity_if_climbing(
-    mut query: Query<(&Climber, &mut GravityScale), Changed<Climber>>
+    mut query: Query<
+        (&Climber, Option<(&StatusEffects, &BaseGravityScale)>, &mut GravityScale),
+        Or<(Changed<Climber>, Changed<StatusEffects>)>
+    >
 ) {
-    for (climber, mut gravity_scale) in &mut query {
+    for (climber, status_effects, mut gravity_scale) in &mut query {
         if climber.climbing {
             gravity_scale.0 = 0.0;
         } else {
-            gravity_scale.0 = 1.0;
+            gravity_scale.0 = status_effects.map_or(1.0, |(status_effects, base)| {
+                base.0 * status_effects.gravity_scale_multiplier()
+            });
         }
     }
 }
@@ -223,8 +223,10 @@ pub struct ColliderBundle {
     pub collision_group: CollisionGroups,
 }
 
-const PLAYER_GROUP: Group = Group::GROUP_1;
-const NPC_GROUP: Group = Group::GROUP_2;
+pub(crate) const PLAYER_GROUP: Group = Group::GROUP_1;
+pub(crate) const NPC_GROUP: Group = Group::GROUP_2;
+pub(crate) const PROJECTILE_GROUP: Group = Group::GROUP_3;
+pub(crate) const ITEM_GROUP: Group = Group::GROUP_4;
 
 impl From<&EntityInstance> for ColliderBundle {
     fn from(entity_instance: &EntityInstance) -> ColliderBundle {
@@ -0,0 +1,115 @@
+//! Crafting recipes.
+//!
+//! Recipes are defined in `data/recipes.recipes.ron`, in order of preference:
+//!
+//! ```ron
+//! [
+//!     (
+//!         id: "healing_potion",
+//!         ingredients: [("Mushroom", 2), ("Water", 1)],
+//!         output: ("HealingPotion", 1),
+//!         brew_time: 10.0,
+//!     ),
+//! ]
+//! ```
+
+use bevy::prelude::*;
+use bevy_asset_loader::{
+    asset_collection::AssetCollection,
+    loading_state::{ config::{ ConfigureLoadingState, LoadingStateConfig }, LoadingStateAppExt },
+};
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::items::{ Inventory, InventoryError, ItemId, ItemRegistry };
+use crate::plugins::{ gamestate::GameState, ron_asset::RonAssetAppExt };
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct Recipe {
+    pub id: String,
+    pub ingredients: Vec<(ItemId, u32)>,
+    pub output: (ItemId, u32),
+    /// Duration in seconds
+    pub brew_time: f32,
+}
+
+impl Recipe {
+    pub fn can_craft(&self, inventory: &Inventory) -> bool {
+        self.ingredients.iter().all(|(item, quantity)| inventory.count(item) >= *quantity)
+    }
+
+    /// Takes the ingredients out of the inventory, or nothing if some are missing
+    pub fn consume(
+        &self,
+        inventory: &mut Inventory,
+        registry: &ItemRegistry
+    ) -> Result<(), CraftingError> {
+        let items = self.ingredients.iter().map(|(item, _)| item).chain([&self.output.0]);
+        for item in items {
+            if registry.get(item).is_none() {
+                return Err(InventoryError::UnknownItem(item.clone()).into());
+            }
+        }
+        for (item, quantity) in &self.ingredients {
+            let count = inventory.count(item);
+            if count < *quantity {
+                return Err(
+                    InventoryError::NotEnough {
+                        item: item.clone(),
+                        missing: quantity - count,
+                    }.into()
+                );
+            }
+        }
+        for (item, quantity) in &self.ingredients {
+            inventory.remove(item, *quantity)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub(crate) enum CraftingError {
+    #[error(transparent)]
+    Inventory(#[from] InventoryError),
+    #[error("no recipe can be crafted with the inventory")]
+    NothingCraftable,
+    #[error("already brewing")]
+    Busy,
+}
+
+#[derive(Asset, TypePath, Deserialize, Debug, Default)]
+#[serde(transparent)]
+pub(crate) struct RecipeBook(pub Vec<Recipe>);
+
+impl RecipeBook {
+    /// The first recipe that can be crafted with the inventory
+    pub fn first_craftable(&self, inventory: &Inventory) -> Option<&Recipe> {
+        self.0.iter().find(|recipe| recipe.can_craft(inventory))
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Recipe> {
+        self.0.iter().find(|recipe| recipe.id == id)
+    }
+
+    /// The recipe following `id` in the book, wrapping around, or the first
+    /// one when none is given
+    pub fn next_after(&self, id: Option<&str>) -> Option<&Recipe> {
+        let next = id
+            .and_then(|id| self.0.iter().position(|recipe| recipe.id == id))
+            .map_or(0, |index| index + 1);
+        self.0.get(next).or_else(|| self.0.first())
+    }
+}
+
+#[derive(AssetCollection, Resource)]
+pub(crate) struct RecipeAssets {
+    #[asset(path = "data/recipes.recipes.ron")]
+    pub book: Handle<RecipeBook>,
+}
+
+pub(crate) fn plugin(app: &mut App) {
+    app.register_ron_asset::<RecipeBook>(&["recipes.ron"]).configure_loading_state(
+        LoadingStateConfig::new(GameState::SplashScreen).load_collection::<RecipeAssets>()
+    );
+}
@@ -0,0 +1,213 @@
+//! Equipment slots and the stats derived from them.
+//!
+//! Equippable items declare their slot and [`StatModifiers`] in the item
+//! registry:
+//!
+//! ```ron
+//! (id: "Helmet", name: "Helmet", icon: "items/helmet.png", category: Equipment, max_stack: 1,
+//!     equipment: Some((slot: Head, modifiers: (armor: 10, move_speed: -0.05)))),
+//! ```
+//!
+//! [`Stats`] are recalculated from [`BaseStats`], the equipment and the status
+//! effects whenever one of them changes, and pushed into [`Armor`] and
+//! [`Health::max`].
+
+use bevy::{ prelude::*, utils::HashMap };
+use serde::Deserialize;
+
+use super::{
+    armor::Armor,
+    health::Health,
+    items::{ Inventory, InventoryError, ItemId, ItemRegistry, Items },
+    status_effects::StatusEffects,
+};
+use crate::plugins::gamestate::GameState;
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EquipmentSlot {
+    Head,
+    Body,
+    Weapon,
+    Trinket,
+}
+
+impl EquipmentSlot {
+    pub const ALL: [Self; 4] = [Self::Head, Self::Body, Self::Weapon, Self::Trinket];
+}
+
+/// Bonuses of an equipped item, speed and damage are fractions (0.1 is +10%)
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct StatModifiers {
+    pub armor: i32,
+    pub max_health: i32,
+    pub move_speed: f32,
+    pub damage: f32,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct EquipmentDefinition {
+    pub slot: EquipmentSlot,
+    #[serde(default)]
+    pub modifiers: StatModifiers,
+}
+
+/// Items equipped by an entity, by slot
+#[derive(Component, Clone, Debug, Default)]
+pub struct Equipment {
+    pub slots: HashMap<EquipmentSlot, ItemId>,
+}
+
+impl Equipment {
+    pub fn get(&self, slot: EquipmentSlot) -> Option<&ItemId> {
+        self.slots.get(&slot)
+    }
+
+    /// Equips an item from the inventory, the previously equipped item of the
+    /// slot goes back to the inventory
+    pub fn equip(
+        &mut self,
+        inventory: &mut Inventory,
+        registry: &ItemRegistry,
+        item: &ItemId
+    ) -> Result<(), InventoryError> {
+        let definition = registry
+            .get(item)
+            .ok_or_else(|| InventoryError::UnknownItem(item.clone()))?;
+        let slot = definition.equipment
+            .ok_or_else(|| InventoryError::NotEquipment(item.clone()))?.slot;
+        inventory.remove(item, 1)?;
+        if let Some(previous) = self.slots.insert(slot, item.clone()) {
+            if let Err(error) = inventory.add(registry, &previous, 1) {
+                // Undo, the inventory only has room for one of the two items
+                self.slots.insert(slot, previous);
+                inventory.add(registry, item, 1)?;
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves the item of the slot back into the inventory
+    pub fn unequip(
+        &mut self,
+        inventory: &mut Inventory,
+        registry: &ItemRegistry,
+        slot: EquipmentSlot
+    ) -> Result<Option<ItemId>, InventoryError> {
+        let Some(item) = self.slots.get(&slot) else {
+            return Ok(None);
+        };
+        inventory.add(registry, item, 1)?;
+        Ok(self.slots.remove(&slot))
+    }
+
+    /// Sum of the modifiers of the equipped items
+    pub fn modifiers(&self, registry: &ItemRegistry) -> StatModifiers {
+        self.slots
+            .values()
+            .filter_map(|item| registry.get(item)?.equipment)
+            .fold(StatModifiers::default(), |total, equipment| {
+                let modifiers = equipment.modifiers;
+                StatModifiers {
+                    armor: total.armor + modifiers.armor,
+                    max_health: total.max_health + modifiers.max_health,
+                    move_speed: total.move_speed + modifiers.move_speed,
+                    damage: total.damage + modifiers.damage,
+                }
+            })
+    }
+}
+
+/// Effective stats of an entity
+#[derive(Component, Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct Stats {
+    /// Damage reduction in percent
+    pub armor: u32,
+    pub max_health: u32,
+    /// Multiplier of the movement speed
+    pub move_speed: f32,
+    /// Multiplier of the dealt damage
+    pub damage: f32,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self {
+            armor: 0,
+            max_health: 100,
+            move_speed: 1.0,
+            damage: 1.0,
+        }
+    }
+}
+
+impl Stats {
+    pub fn scale_damage(&self, damage: u32) -> u32 {
+        ((damage as f32) * self.damage).round() as u32
+    }
+}
+
+/// Stats of an entity before equipment and status effects
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Deref, DerefMut)]
+pub struct BaseStats(pub Stats);
+
+pub(crate) fn recalculate_stats(
+    mut query: Query<
+        (
+            &BaseStats,
+            Option<&Equipment>,
+            Option<&StatusEffects>,
+            &mut Stats,
+            Option<&mut Armor>,
+            Option<&mut Health>,
+        ),
+        Or<(Changed<BaseStats>, Changed<Equipment>, Changed<StatusEffects>)>
+    >,
+    items: Items
+) {
+    let Some(registry) = items.registry() else {
+        return;
+    };
+    for (base, equipment, status_effects, mut stats, armor, health) in &mut query {
+        let modifiers = equipment.map_or_else(StatModifiers::default, |equipment|
+            equipment.modifiers(registry)
+        );
+        let armor_bonus = status_effects.map_or(0, StatusEffects::armor_bonus);
+        let new_stats = Stats {
+            armor: ((base.armor as i32) + modifiers.armor + armor_bonus).clamp(0, 100) as u32,
+            max_health: ((base.max_health as i32) + modifiers.max_health).max(1) as u32,
+            move_speed: (
+                base.move_speed *
+                (1.0 + modifiers.move_speed) *
+                status_effects.map_or(1.0, StatusEffects::move_speed_multiplier)
+            ).max(0.0),
+            damage: (
+                base.damage *
+                (1.0 + modifiers.damage) *
+                status_effects.map_or(1.0, StatusEffects::damage_multiplier)
+            ).max(0.0),
+        };
+        if *stats == new_stats {
+            continue;
+        }
+        *stats = new_stats;
+        if let Some(mut armor) = armor {
+            armor.value = stats.armor;
+        }
+        if let Some(mut health) = health {
+            health.max = stats.max_health;
+            health.current = health.current.min(health.max);
+        }
+    }
+}
+
+pub(crate) fn plugin(app: &mut App) {
+    app.register_type::<Stats>().add_systems(
+        Update,
+        recalculate_stats
+            .after(super::status_effects::tick_status_effects)
+            .before(super::health::apply_damage)
+            .run_if(in_state(GameState::Playing))
+    );
+}
@@ -0,0 +1,28 @@
+use bevy::prelude::*;
+use bevy_rapier2d::dynamics::Velocity;
+
+/// Horizontal direction an entity is looking at, updated from its velocity.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct Facing(pub Vec2);
+
+impl Default for Facing {
+    fn default() -> Self {
+        Self(Vec2::X)
+    }
+}
+
+/// Turns entities toward their horizontal movement and flips their sprite
+pub fn update_facing(mut query: Query<(&Velocity, &mut Facing, Option<&mut Sprite>)>) {
+    for (velocity, mut facing, sprite) in &mut query {
+        if velocity.linvel.x.abs() < 1.0 {
+            continue;
+        }
+        let direction = Vec2::new(velocity.linvel.x.signum(), 0.0);
+        if facing.0 != direction {
+            facing.0 = direction;
+            if let Some(mut sprite) = sprite {
+                sprite.flip_x = direction.x < 0.0;
+            }
+        }
+    }
+}
@@ -1,6 +1,17 @@
-use bevy::{ ecs::component::Component, reflect::Reflect };
+use bevy::{
+    app::{ App, Update },
+    ecs::{
+        component::Component,
+        entity::Entity,
+        event::{ Event, EventReader, EventWriter },
+        schedule::{ common_conditions::in_state, IntoSystemConfigs },
+        system::Query,
+    },
+    reflect::Reflect,
+};
 
 use super::armor::Armor;
+use crate::plugins::gamestate::GameState;
 
 // 🩸
 #[derive(Component, Clone, Reflect)]
@@ -29,4 +40,71 @@ impl Health {
 
         self.current = self.current.saturating_sub(damage);
     }
+
+    pub fn heal(&mut self, amount: u32) {
+        self.current = self.current.saturating_add(amount).min(self.max);
+    }
+}
+
+/// Request to damage an entity, armor is applied by [`apply_damage`].
+#[derive(Event, Clone, Copy, Debug)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: u32,
+    /// The entity responsible for the damage, if any
+    pub source: Option<Entity>,
+}
+
+/// Sent when the health of an entity drops to 0
+#[derive(Event, Clone, Copy, Debug)]
+pub struct Died {
+    pub entity: Entity,
+    /// The entity that dealt the fatal damage, if any
+    pub killer: Option<Entity>,
+}
+
+/// Request to heal an entity, capped by [`Health::max`].
+#[derive(Event, Clone, Copy, Debug)]
+pub struct HealEvent {
+    pub target: Entity,
+    pub amount: u32,
+}
+
+pub(crate) fn apply_damage(
+    mut damage_events: EventReader<DamageEvent>,
+    mut died_events: EventWriter<Died>,
+    mut query: Query<(&mut Health, Option<&Armor>)>
+) {
+    for event in damage_events.read() {
+        if let Ok((mut health, armor)) = query.get_mut(event.target) {
+            if health.current == 0 {
+                continue;
+            }
+            health.take_damage(event.amount, armor);
+            if health.current == 0 {
+                died_events.send(Died {
+                    entity: event.target,
+                    killer: event.source,
+                });
+            }
+        }
+    }
+}
+
+pub(crate) fn apply_heal(mut heal_events: EventReader<HealEvent>, mut query: Query<&mut Health>) {
+    for event in heal_events.read() {
+        if let Ok(mut health) = query.get_mut(event.target) {
+            health.heal(event.amount);
+        }
+    }
+}
+
+pub(crate) fn plugin(app: &mut App) {
+    app.add_event::<DamageEvent>()
+        .add_event::<HealEvent>()
+        .add_event::<Died>()
+        .add_systems(
+            Update,
+            (apply_damage, apply_heal).run_if(in_state(GameState::Playing))
+        );
 }
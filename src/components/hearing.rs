@@ -0,0 +1,234 @@
+//! Noises and the AI actors hearing them.
+//!
+//! Footsteps, landings, abilities and destroyed entities send [`NoiseEvent`]s.
+//! Every [`Hearing`] actor perceives them with an intensity fading with the
+//! distance, and muffled by each wall between the noise and the actor.
+
+use bevy::prelude::*;
+use bevy_rapier2d::{ dynamics::Velocity, pipeline::QueryFilter, plugin::RapierContext };
+use input_manager::action_state::ActionState;
+
+use super::{ ground::GroundDetection, health::Died };
+use crate::{ entities::player::Ability, plugins::gamestate::GameState };
+
+/// Intensity left after going through a wall
+const WALL_DAMPING: f32 = 0.4;
+/// Noise of something being destroyed
+const BREAKING_LOUDNESS: f32 = 200.0;
+
+#[derive(Event, Clone, Copy, Debug)]
+pub struct NoiseEvent {
+    pub position: Vec2,
+    /// Distance in pixels the noise carries in the open
+    pub loudness: f32,
+    pub source: Option<Entity>,
+}
+
+/// A noise as perceived by an actor
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct HeardNoise {
+    pub position: Vec2,
+    /// From 0 to 1
+    pub intensity: f32,
+    pub source: Option<Entity>,
+}
+
+#[derive(Component, Clone, Debug, Reflect)]
+pub struct Hearing {
+    /// Multiplier of the distance noises carry
+    pub sensitivity: f32,
+    /// Intensity under which noises go unnoticed
+    pub threshold: f32,
+    /// Loudest noise heard during the last frame
+    pub heard: Option<HeardNoise>,
+    /// Last noise heard at all
+    pub last_heard: Option<HeardNoise>,
+}
+
+impl Default for Hearing {
+    fn default() -> Self {
+        Self {
+            sensitivity: 1.0,
+            threshold: 0.1,
+            heard: None,
+            last_heard: None,
+        }
+    }
+}
+
+/// Makes noise while walking, and when landing
+#[derive(Component, Clone, Debug)]
+pub struct Footsteps {
+    /// Distance between two steps
+    pub stride: f32,
+    pub loudness: f32,
+    travelled: f32,
+    fall_speed: f32,
+    was_on_ground: bool,
+}
+
+impl Default for Footsteps {
+    fn default() -> Self {
+        Self {
+            stride: 32.0,
+            loudness: 80.0,
+            travelled: 0.0,
+            fall_speed: 0.0,
+            was_on_ground: true,
+        }
+    }
+}
+
+/// Intensity of a noise heard at `listener`, 0 if it can't be heard
+fn perceive(
+    rapier_context: &RapierContext,
+    hearing: &Hearing,
+    listener: Vec2,
+    noise: &NoiseEvent
+) -> f32 {
+    let vector = noise.position - listener;
+    let distance = vector.length();
+    let range = noise.loudness * hearing.sensitivity;
+    if distance >= range {
+        return 0.0;
+    }
+    let mut intensity = 1.0 - distance / range;
+    if distance > f32::EPSILON {
+        rapier_context.intersections_with_ray(
+            listener,
+            vector / distance,
+            distance,
+            true,
+            QueryFilter::only_fixed().exclude_sensors(),
+            |_, _| {
+                intensity *= WALL_DAMPING;
+                true
+            }
+        );
+    }
+    intensity
+}
+
+pub(crate) fn hear_noises(
+    mut noise_events: EventReader<NoiseEvent>,
+    mut listeners: Query<(Entity, &GlobalTransform, &mut Hearing)>,
+    rapier_context: Res<RapierContext>
+) {
+    let noises: Vec<_> = noise_events.read().copied().collect();
+    for (entity, transform, mut hearing) in &mut listeners {
+        if hearing.heard.is_some() {
+            hearing.heard = None;
+        }
+        let listener = transform.translation().truncate();
+        let loudest = noises
+            .iter()
+            .filter(|noise| noise.source != Some(entity))
+            .map(|noise| (noise, perceive(&rapier_context, &hearing, listener, noise)))
+            .filter(|(_, intensity)| *intensity >= hearing.threshold)
+            .max_by(|(_, a), (_, b)| a.total_cmp(b));
+        if let Some((noise, intensity)) = loudest {
+            trace!("{:?} heard a noise at {} ({})", entity, noise.position, intensity);
+            let heard = HeardNoise {
+                position: noise.position,
+                intensity,
+                source: noise.source,
+            };
+            hearing.heard = Some(heard);
+            hearing.last_heard = Some(heard);
+        }
+    }
+}
+
+pub(crate) fn emit_footsteps(
+    time: Res<Time>,
+    mut query: Query<(Entity, &GlobalTransform, &Velocity, &GroundDetection, &mut Footsteps)>,
+    mut noise_events: EventWriter<NoiseEvent>
+) {
+    for (entity, transform, velocity, ground_detection, mut footsteps) in &mut query {
+        let position = transform.translation().truncate();
+        if !ground_detection.on_ground {
+            footsteps.fall_speed = footsteps.fall_speed.max(-velocity.linvel.y);
+            footsteps.was_on_ground = false;
+            continue;
+        }
+        if !footsteps.was_on_ground {
+            // Harder landings are louder
+            let loudness = footsteps.loudness * (1.0 + footsteps.fall_speed.max(0.0) / 300.0);
+            noise_events.send(NoiseEvent { position, loudness, source: Some(entity) });
+            footsteps.fall_speed = 0.0;
+            footsteps.travelled = 0.0;
+            footsteps.was_on_ground = true;
+            continue;
+        }
+        footsteps.travelled += velocity.linvel.x.abs() * time.delta_seconds();
+        if footsteps.travelled >= footsteps.stride {
+            footsteps.travelled = 0.0;
+            noise_events.send(NoiseEvent {
+                position,
+                loudness: footsteps.loudness,
+                source: Some(entity),
+            });
+        }
+    }
+}
+
+fn ability_loudness(ability: &Ability) -> f32 {
+    match ability {
+        Ability::Heal => 40.0,
+        Ability::Slash => 60.0,
+        Ability::Dash => 80.0,
+        Ability::Shoot => 120.0,
+        Ability::FrozenOrb | Ability::PolymorphSheep => 150.0,
+        Ability::Fireball => 200.0,
+        Ability::LightningBolt => 250.0,
+    }
+}
+
+pub(crate) fn emit_ability_noises(
+    casters: Query<(Entity, &GlobalTransform, &ActionState<Ability>)>,
+    mut noise_events: EventWriter<NoiseEvent>
+) {
+    for (entity, transform, ability_state) in &casters {
+        for ability in ability_state.get_just_pressed() {
+            noise_events.send(NoiseEvent {
+                position: transform.translation().truncate(),
+                loudness: ability_loudness(&ability),
+                source: Some(entity),
+            });
+        }
+    }
+}
+
+pub(crate) fn emit_breaking_noises(
+    mut died_events: EventReader<Died>,
+    transforms: Query<&GlobalTransform>,
+    mut noise_events: EventWriter<NoiseEvent>
+) {
+    for Died { entity, .. } in died_events.read() {
+        if let Ok(transform) = transforms.get(*entity) {
+            noise_events.send(NoiseEvent {
+                position: transform.translation().truncate(),
+                loudness: BREAKING_LOUDNESS,
+                source: Some(*entity),
+            });
+        }
+    }
+}
+
+pub(crate) fn plugin(app: &mut App) {
+    app.add_event::<NoiseEvent>()
+        .register_type::<Hearing>()
+        .add_systems(
+            Update,
+            (
+                (
+                    emit_footsteps,
+                    emit_ability_noises,
+                    emit_breaking_noises.before(crate::entities::enemy::despawn_dead_enemies),
+                ),
+                hear_noises,
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing))
+        );
+}
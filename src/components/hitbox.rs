@@ -0,0 +1,633 @@
+//! Frame accurate melee combat, authored in Aseprite.
+//!
+//! Hitboxes and hurtboxes are Aseprite slices named `hitbox…` and `hurtbox…`.
+//! A slice key applies from its frame until the next key, a key with an empty
+//! bound disables the box. They are read from the [`Aseprite`] files loaded by
+//! `bevy_mod_aseprite`, along with the frame durations and tags.
+//!
+//! Entities animated by Aseprite get the boxes of their own file, LDtk
+//! entities the ones of the file in their `frame_boxes` field. Each slice is
+//! converted into a sensor collider child, that is only enabled on the frames
+//! it appears in, following the displayed [`AsepriteAnimation`].
+//!
+//! The frames of an attack having a hitbox are its active window, the ones
+//! before are the startup and the ones after the recovery. Hits only land
+//! while active, and the attacker can't move nor cancel the attack otherwise.
+
+use bevy::{ prelude::*, utils::HashMap };
+use bevy_ecs_ldtk::{ prelude::LdtkFields, EntityInstance };
+use bevy_mod_aseprite::{ Aseprite, AsepriteAnimation };
+use bevy_rapier2d::{
+    geometry::{ ActiveCollisionTypes, Collider, ColliderDisabled, ColliderMassProperties, Sensor },
+    plugin::RapierContext,
+};
+use input_manager::action_state::ActionState;
+
+use super::{ equipment::Stats, facing::Facing, health::{ DamageEvent, Health } };
+use crate::{
+    entities::player::Ability,
+    plugins::{ dialogueview::not_in_dialogue, gamestate::GameState },
+};
+
+/// Used for the frames without a known duration, in seconds
+const DEFAULT_FRAME_DURATION: f32 = 0.1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum FrameBoxKind {
+    Hitbox,
+    Hurtbox,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct FrameBox {
+    pub kind: FrameBoxKind,
+    pub name: String,
+    /// Bounds relative to the center of the sprite, Y up
+    pub rect: Rect,
+}
+
+/// Phase of an attack animation, derived from the frames having hitboxes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum AttackPhase {
+    Startup,
+    Active,
+    Recovery,
+}
+
+/// Per frame hitboxes and hurtboxes of a sprite
+#[derive(Debug, Default)]
+pub(crate) struct FrameBoxes {
+    /// Duration of each frame, in seconds
+    pub durations: Vec<f32>,
+    pub frames: Vec<Vec<FrameBox>>,
+    /// Frame ranges of the Aseprite tags
+    pub tags: HashMap<String, (usize, usize)>,
+}
+
+impl FrameBoxes {
+    pub fn boxes(&self, frame: usize) -> &[FrameBox] {
+        self.frames.get(frame).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn is_active(&self, frame: usize) -> bool {
+        self.boxes(frame)
+            .iter()
+            .any(|frame_box| frame_box.kind == FrameBoxKind::Hitbox)
+    }
+
+    /// Phase of `frame` in the animation tagged `tag`
+    pub fn phase(&self, tag: &str, frame: usize) -> Option<AttackPhase> {
+        let &(from, to) = self.tags.get(tag)?;
+        if self.is_active(frame) {
+            Some(AttackPhase::Active)
+        } else if (from..frame).any(|previous| self.is_active(previous)) {
+            Some(AttackPhase::Recovery)
+        } else if (frame..=to).any(|next| self.is_active(next)) {
+            Some(AttackPhase::Startup)
+        } else {
+            Some(AttackPhase::Recovery)
+        }
+    }
+
+    /// Every distinct box, used to spawn one collider per slice
+    fn distinct_boxes(&self) -> Vec<(FrameBoxKind, String)> {
+        let mut boxes = Vec::new();
+        for frame_box in self.frames.iter().flatten() {
+            let key = (frame_box.kind, frame_box.name.clone());
+            if !boxes.contains(&key) {
+                boxes.push(key);
+            }
+        }
+        boxes
+    }
+
+    /// Converts the `hitbox…` and `hurtbox…` slices of a loaded Aseprite file
+    fn from_aseprite(aseprite: &Aseprite) -> Option<Self> {
+        let info = aseprite.info().as_ref()?;
+        let frame_count = info.frame_count;
+        let (width, height) = info.dimensions;
+        let mut frames = vec![Vec::new(); frame_count];
+
+        for (name, slice) in &info.slices {
+            let kind = if name.starts_with("hitbox") {
+                FrameBoxKind::Hitbox
+            } else if name.starts_with("hurtbox") {
+                FrameBoxKind::Hurtbox
+            } else {
+                continue;
+            };
+            let mut keys = slice.keys.iter().collect::<Vec<_>>();
+            keys.sort_by_key(|key| key.from_frame);
+            for (index, key) in keys.iter().enumerate() {
+                let (x, y) = key.origin;
+                let (w, h) = key.size;
+                if w == 0 || h == 0 {
+                    continue;
+                }
+                let from = key.from_frame as usize;
+                let until = keys.get(index + 1).map_or(frame_count, |next| next.from_frame as usize);
+                // Aseprite coordinates start from the top left corner
+                let center = Vec2::new(
+                    (x as f32) + (w as f32) / 2.0 - (width as f32) / 2.0,
+                    (height as f32) / 2.0 - (y as f32) - (h as f32) / 2.0
+                );
+                for frame in from..until.min(frame_count) {
+                    frames[frame].push(FrameBox {
+                        kind,
+                        name: name.clone(),
+                        rect: Rect::from_center_size(center, Vec2::new(w as f32, h as f32)),
+                    });
+                }
+            }
+        }
+
+        Some(FrameBoxes {
+            durations: info.frame_infos
+                .iter()
+                .map(|frame| (frame.delay_ms as f32) / 1000.0)
+                .collect(),
+            frames,
+            tags: info.tags
+                .iter()
+                .map(|(name, tag)| {
+                    let frames = &tag.frames;
+                    (name.clone(), (frames.start as usize, frames.end.saturating_sub(1) as usize))
+                })
+                .collect(),
+        })
+    }
+
+    /// Duration of `frame`, in seconds
+    fn duration(&self, frame: usize) -> f32 {
+        self.durations.get(frame).copied().unwrap_or(DEFAULT_FRAME_DURATION)
+    }
+}
+
+/// Frame boxes of the loaded Aseprite files
+#[derive(Resource, Debug, Default)]
+pub(crate) struct FrameBoxLibrary {
+    boxes: HashMap<AssetId<Aseprite>, FrameBoxes>,
+}
+
+impl FrameBoxLibrary {
+    pub fn get(&self, aseprite: &Handle<Aseprite>) -> Option<&FrameBoxes> {
+        self.boxes.get(&aseprite.id())
+    }
+}
+
+/// The frame boxes of an entity, from its Aseprite file
+#[derive(Component, Clone, Debug)]
+pub(crate) struct FrameBoxSource {
+    pub aseprite: Handle<Aseprite>,
+}
+
+/// Frame currently displayed by an entity having [`FrameBoxSource`]
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Deref, DerefMut)]
+pub(crate) struct CurrentFrame(pub usize);
+
+/// Sensor collider child converted from a slice
+#[derive(Component, Clone, Debug)]
+pub(crate) struct FrameBoxCollider {
+    pub owner: Entity,
+    pub kind: FrameBoxKind,
+    pub name: String,
+}
+
+/// Marker of the entities having hurtbox colliders, their body collider is
+/// then ignored by hitboxes
+#[derive(Component)]
+pub(crate) struct HasHurtboxes;
+
+/// A melee attack playing the frames of an Aseprite tag
+#[derive(Component, Debug)]
+pub(crate) struct MeleeAttack {
+    pub tag: &'static str,
+    pub damage: u32,
+    pub end: usize,
+    /// Phase of the current frame, hits only land while active
+    pub phase: AttackPhase,
+    /// Frame shown before the attack, by entities without animation
+    return_frame: usize,
+    /// Animation displayed before the attack, restored once it's over. Entities
+    /// without an [`AsepriteAnimation`] step through the frames on their own.
+    previous: Option<AsepriteAnimation>,
+    /// Until the next frame, or until the end of the tag when animated
+    timer: Timer,
+    hits: Vec<Entity>,
+}
+
+impl MeleeAttack {
+    /// Winding up or recovering, the attacker can neither move nor use another
+    /// ability
+    pub fn is_committed(&self) -> bool {
+        matches!(self.phase, AttackPhase::Startup | AttackPhase::Recovery)
+    }
+}
+
+/// The animation tag and damage of a melee ability, if it is one
+fn melee_for_ability(ability: Ability) -> Option<(&'static str, u32)> {
+    match ability {
+        Ability::Slash => Some(("slash", 15)),
+        _ => None,
+    }
+}
+
+/// Inserts [`FrameBoxSource`] into the LDtk entities with a `frame_boxes` field
+/// (path of the Aseprite file), and into the entities animated by Aseprite
+pub(crate) fn insert_frame_box_sources(
+    mut commands: Commands,
+    entity_instances: Query<(Entity, &EntityInstance), Added<EntityInstance>>,
+    animated: Query<(Entity, &Handle<Aseprite>), (Added<Handle<Aseprite>>, Without<FrameBoxSource>)>,
+    asset_server: Res<AssetServer>
+) {
+    for (entity, entity_instance) in &entity_instances {
+        if let Ok(path) = entity_instance.get_string_field("frame_boxes") {
+            commands.entity(entity).insert((
+                FrameBoxSource { aseprite: asset_server.load(path.clone()) },
+                CurrentFrame::default(),
+            ));
+        }
+    }
+    for (entity, aseprite) in &animated {
+        commands
+            .entity(entity)
+            .insert((FrameBoxSource { aseprite: aseprite.clone() }, CurrentFrame::default()));
+    }
+}
+
+/// Converts the slices of the Aseprite files once loaded, or reloaded
+pub(crate) fn build_frame_boxes(
+    mut events: EventReader<AssetEvent<Aseprite>>,
+    aseprites: Res<Assets<Aseprite>>,
+    mut library: ResMut<FrameBoxLibrary>
+) {
+    for event in events.read() {
+        match event {
+            AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => {
+                if let Some(boxes) = aseprites.get(*id).and_then(FrameBoxes::from_aseprite) {
+                    library.boxes.insert(*id, boxes);
+                }
+            }
+            AssetEvent::Removed { id } => {
+                library.boxes.remove(id);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Converts the slices into disabled sensor colliders, once loaded
+pub(crate) fn spawn_frame_box_colliders(
+    mut commands: Commands,
+    sources: Query<(Entity, &FrameBoxSource, Option<&Children>)>,
+    colliders: Query<(), With<FrameBoxCollider>>,
+    library: Res<FrameBoxLibrary>,
+    added: Query<(), Added<FrameBoxSource>>,
+    mut pending: Local<Vec<Entity>>
+) {
+    if library.is_changed() {
+        // Hot reloading, or sources added before their file was loaded
+        pending.extend(sources.iter().map(|(entity, ..)| entity));
+    }
+    pending.extend(added.iter_entities());
+    pending.sort_unstable();
+    pending.dedup();
+
+    pending.retain(|&entity| {
+        let Ok((entity, source, children)) = sources.get(entity) else {
+            return false;
+        };
+        let Some(boxes) = library.get(&source.aseprite) else {
+            // Not loaded yet
+            return true;
+        };
+        // Replace the previous colliders
+        for &child in children.iter().flat_map(|children| children.iter()) {
+            if colliders.contains(child) {
+                commands.entity(child).despawn_recursive();
+            }
+        }
+        let distinct_boxes = boxes.distinct_boxes();
+        if distinct_boxes.iter().any(|(kind, _)| *kind == FrameBoxKind::Hurtbox) {
+            commands.entity(entity).insert(HasHurtboxes);
+        } else {
+            commands.entity(entity).remove::<HasHurtboxes>();
+        }
+        commands.entity(entity).with_children(|parent| {
+            for (kind, name) in distinct_boxes {
+                parent.spawn((
+                    Name::new(name.clone()),
+                    FrameBoxCollider { owner: entity, kind, name },
+                    Collider::cuboid(0.5, 0.5),
+                    Sensor,
+                    // Bodies are kinematic, the sensors have to be told to
+                    // detect each other
+                    ActiveCollisionTypes::all(),
+                    ColliderDisabled,
+                    ColliderMassProperties::Density(0.0),
+                    TransformBundle::default(),
+                ));
+            }
+        });
+        false
+    });
+}
+
+pub(crate) fn start_melee_attacks(
+    mut commands: Commands,
+    mut query: Query<
+        (
+            Entity,
+            &ActionState<Ability>,
+            &FrameBoxSource,
+            &CurrentFrame,
+            Option<&Stats>,
+            Option<&mut AsepriteAnimation>,
+        ),
+        Without<MeleeAttack>
+    >,
+    library: Res<FrameBoxLibrary>
+) {
+    for (entity, ability_state, source, frame, stats, animation) in &mut query {
+        let Some(boxes) = library.get(&source.aseprite) else {
+            continue;
+        };
+        for ability in ability_state.get_just_pressed() {
+            let Some((tag, damage)) = melee_for_ability(ability) else {
+                continue;
+            };
+            let Some(&(from, to)) = boxes.tags.get(tag) else {
+                warn!("{:?} has no {:?} animation", entity, tag);
+                continue;
+            };
+            let (previous, duration) = match animation {
+                Some(mut animation) => {
+                    let previous = std::mem::replace(&mut *animation, AsepriteAnimation::from(tag));
+                    (Some(previous), (from..=to).map(|frame| boxes.duration(frame)).sum())
+                }
+                None => (None, boxes.duration(from)),
+            };
+            commands.entity(entity).insert((
+                MeleeAttack {
+                    tag,
+                    damage: stats.map_or(damage, |stats| stats.scale_damage(damage)),
+                    end: to,
+                    phase: boxes.phase(tag, from).unwrap_or(AttackPhase::Startup),
+                    return_frame: **frame,
+                    previous,
+                    timer: Timer::from_seconds(duration, TimerMode::Once),
+                    hits: Vec::new(),
+                },
+                CurrentFrame(from),
+            ));
+            break;
+        }
+    }
+}
+
+/// Ends the melee attacks once their tag has played, restoring the previous
+/// animation. The frames of entities without animation are stepped here.
+pub(crate) fn advance_melee_attacks(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<
+        (Entity, &mut MeleeAttack, &mut CurrentFrame, &FrameBoxSource, Option<&mut AsepriteAnimation>)
+    >,
+    library: Res<FrameBoxLibrary>
+) {
+    for (entity, mut attack, mut frame, source, animation) in &mut query {
+        let boxes = library.get(&source.aseprite);
+        if !attack.timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+        if let Some(previous) = attack.previous.take() {
+            if let Some(mut animation) = animation {
+                *animation = previous;
+            }
+            commands.entity(entity).remove::<MeleeAttack>();
+            continue;
+        }
+        if **frame >= attack.end {
+            commands.entity(entity).remove::<MeleeAttack>();
+            **frame = attack.return_frame;
+            continue;
+        }
+        **frame += 1;
+        let duration = boxes.map_or(DEFAULT_FRAME_DURATION, |boxes| boxes.duration(**frame));
+        attack.timer = Timer::from_seconds(duration, TimerMode::Once);
+    }
+}
+
+/// Follows the frame displayed by the Aseprite animation
+pub(crate) fn sync_current_frame(
+    mut query: Query<(&AsepriteAnimation, &mut CurrentFrame), Changed<AsepriteAnimation>>
+) {
+    for (animation, mut frame) in &mut query {
+        frame.set_if_neq(CurrentFrame(animation.current_frame()));
+    }
+}
+
+/// Startup, active and recovery windows of the melee attacks, from their
+/// current frame
+pub(crate) fn update_attack_phases(
+    mut query: Query<(Entity, &mut MeleeAttack, &CurrentFrame, &FrameBoxSource), Changed<CurrentFrame>>,
+    library: Res<FrameBoxLibrary>
+) {
+    for (entity, mut attack, frame, source) in &mut query {
+        let Some(phase) = library
+            .get(&source.aseprite)
+            .and_then(|boxes| boxes.phase(attack.tag, **frame)) else {
+            continue;
+        };
+        if attack.phase != phase {
+            trace!("{:?} {} frame {}: {:?}", entity, attack.tag, **frame, phase);
+            attack.phase = phase;
+        }
+    }
+}
+
+/// Enables the colliders of the boxes present on the current frame
+pub(crate) fn sync_frame_box_colliders(
+    mut commands: Commands,
+    sources: Query<
+        (&FrameBoxSource, &CurrentFrame, Option<&Facing>),
+        Or<(Changed<CurrentFrame>, Changed<Facing>)>
+    >,
+    mut colliders: Query<(Entity, &FrameBoxCollider, &mut Collider, &mut Transform)>,
+    library: Res<FrameBoxLibrary>
+) {
+    for (entity, frame_box_collider, mut collider, mut transform) in &mut colliders {
+        let Ok((source, frame, facing)) = sources.get(frame_box_collider.owner) else {
+            continue;
+        };
+        let Some(boxes) = library.get(&source.aseprite) else {
+            continue;
+        };
+        let frame_box = boxes
+            .boxes(**frame)
+            .iter()
+            .find(|frame_box| {
+                frame_box.kind == frame_box_collider.kind && frame_box.name == frame_box_collider.name
+            });
+        let Some(frame_box) = frame_box else {
+            commands.entity(entity).insert(ColliderDisabled);
+            continue;
+        };
+        let mut center = frame_box.rect.center();
+        // Sprites are flipped when facing left
+        if facing.is_some_and(|facing| facing.0.x < 0.0) {
+            center.x = -center.x;
+        }
+        let half_size = frame_box.rect.half_size();
+        *collider = Collider::cuboid(half_size.x, half_size.y);
+        transform.translation = center.extend(transform.translation.z);
+        commands.entity(entity).remove::<ColliderDisabled>();
+    }
+}
+
+/// Damages the bodies and hurtboxes the enabled hitbox colliders of attacking
+/// entities intersect
+pub(crate) fn resolve_melee_hits(
+    rapier_context: Res<RapierContext>,
+    hitboxes: Query<(Entity, &FrameBoxCollider), Without<ColliderDisabled>>,
+    mut attackers: Query<&mut MeleeAttack>,
+    hurtboxes: Query<&FrameBoxCollider>,
+    bodies: Query<(), (With<Health>, Without<HasHurtboxes>)>,
+    mut damage_events: EventWriter<DamageEvent>
+) {
+    for (hitbox, frame_box_collider) in &hitboxes {
+        if frame_box_collider.kind != FrameBoxKind::Hitbox {
+            continue;
+        }
+        let attacker = frame_box_collider.owner;
+        let Ok(mut attack) = attackers.get_mut(attacker) else {
+            continue;
+        };
+        // Boxes lingering outside of the active frames don't hit
+        if attack.phase != AttackPhase::Active {
+            continue;
+        }
+        for (collider1, collider2, intersecting) in rapier_context.intersection_pairs_with(hitbox) {
+            if !intersecting {
+                continue;
+            }
+            let other = if collider1 == hitbox { collider2 } else { collider1 };
+            let target = match hurtboxes.get(other) {
+                Ok(hurtbox) if hurtbox.kind == FrameBoxKind::Hurtbox => hurtbox.owner,
+                Ok(_) => continue,
+                Err(_) if bodies.contains(other) => other,
+                Err(_) => continue,
+            };
+            if target == attacker || attack.hits.contains(&target) {
+                continue;
+            }
+            attack.hits.push(target);
+            damage_events.send(DamageEvent {
+                target,
+                amount: attack.damage,
+                source: Some(attacker),
+            });
+        }
+    }
+}
+
+pub(crate) fn plugin(app: &mut App) {
+    app.init_resource::<FrameBoxLibrary>().add_systems(
+        Update,
+        (
+            insert_frame_box_sources,
+            build_frame_boxes,
+            spawn_frame_box_colliders,
+            start_melee_attacks.run_if(not_in_dialogue),
+            advance_melee_attacks,
+            sync_current_frame,
+            update_attack_phases,
+            sync_frame_box_colliders,
+            resolve_melee_hits,
+        )
+            .chain()
+            .before(super::health::apply_damage)
+            .run_if(in_state(GameState::Playing))
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hitbox() -> FrameBox {
+        FrameBox {
+            kind: FrameBoxKind::Hitbox,
+            name: "hitbox".into(),
+            rect: Rect::new(0.0, 0.0, 8.0, 8.0),
+        }
+    }
+
+    fn hurtbox() -> FrameBox {
+        FrameBox {
+            kind: FrameBoxKind::Hurtbox,
+            name: "hurtbox".into(),
+            rect: Rect::new(-4.0, -8.0, 4.0, 8.0),
+        }
+    }
+
+    /// An idle frame, then a slash having its hitbox on its third and fourth
+    /// frames
+    fn boxes() -> FrameBoxes {
+        let mut frames = vec![vec![hurtbox()]; 7];
+        frames[3].push(hitbox());
+        frames[4].push(hitbox());
+        FrameBoxes {
+            durations: vec![0.1; 7],
+            frames,
+            tags: [("idle".to_string(), (0, 0)), ("slash".to_string(), (1, 6))].into_iter().collect(),
+        }
+    }
+
+    #[test]
+    fn phases_follow_the_hitbox_frames() {
+        let boxes = boxes();
+        let phases = (1..=6).map(|frame| boxes.phase("slash", frame)).collect::<Vec<_>>();
+        assert_eq!(phases, vec![
+            Some(AttackPhase::Startup),
+            Some(AttackPhase::Startup),
+            Some(AttackPhase::Active),
+            Some(AttackPhase::Active),
+            Some(AttackPhase::Recovery),
+            Some(AttackPhase::Recovery),
+        ]);
+        assert_eq!(boxes.phase("kick", 3), None);
+    }
+
+    #[test]
+    fn tags_without_hitbox_never_activate() {
+        assert_eq!(boxes().phase("idle", 0), Some(AttackPhase::Recovery));
+    }
+
+    #[test]
+    fn one_collider_per_slice() {
+        assert_eq!(boxes().distinct_boxes(), vec![
+            (FrameBoxKind::Hurtbox, "hurtbox".to_string()),
+            (FrameBoxKind::Hitbox, "hitbox".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn only_windups_and_recoveries_commit() {
+        let mut attack = MeleeAttack {
+            tag: "slash",
+            damage: 15,
+            end: 6,
+            phase: AttackPhase::Startup,
+            return_frame: 0,
+            previous: None,
+            timer: Timer::from_seconds(0.1, TimerMode::Once),
+            hits: Vec::new(),
+        };
+        assert!(attack.is_committed());
+        attack.phase = AttackPhase::Active;
+        assert!(!attack.is_committed());
+        attack.phase = AttackPhase::Recovery;
+        assert!(attack.is_committed());
+    }
+}
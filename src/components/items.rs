@@ -1,17 +1,347 @@
+//! Item registry and inventories.
+//!
+//! Items are defined in `data/items.items.ron`, their id is the identifier of
+//! the value in the LDtk `Item` enum:
+//!
+//! ```ron
+//! [
+//!     (id: "Knife", name: "Knife", icon: "items/knife.png", category: Equipment, max_stack: 1, tags: ["weapon"]),
+//!     (id: "Mushroom", name: "Mushroom", icon: "items/mushroom.png", category: Material, max_stack: 20),
+//! ]
+//! ```
+
 use bevy::{
+    app::{ App, Update },
+    asset::{ Asset, Assets, Handle },
+    ecs::{
+        component::Component,
+        entity::Entity,
+        event::{ Event, EventReader, EventWriter },
+        query::{ Added, With },
+        schedule::{ common_conditions::in_state, IntoSystemConfigs },
+        system::{ Query, Res, Resource, SystemParam },
+    },
     input::{ ButtonInput, keyboard::KeyCode },
-    ecs::{ component::Component, system::Res, system::Query, query::With },
+    log,
+    reflect::TypePath,
+    utils::HashMap,
+};
+use bevy_asset_loader::{
+    asset_collection::AssetCollection,
+    loading_state::{ config::{ ConfigureLoadingState, LoadingStateConfig }, LoadingStateAppExt },
 };
 use bevy_ecs_ldtk::{ ldtk::ldtk_fields::LdtkFields, EntityInstance };
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::{
+    equipment::EquipmentDefinition,
+    health::HealEvent,
+    status_effects::ApplyStatusEffect,
+};
+use crate::{ entities::Player, plugins::{ gamestate::GameState, ron_asset::RonAssetAppExt } };
+
+/// Identifier of an item of the [`ItemRegistry`]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize)]
+#[serde(transparent)]
+pub struct ItemId(pub String);
+
+impl From<&str> for ItemId {
+    fn from(id: &str) -> Self {
+        Self(id.to_owned())
+    }
+}
+
+impl std::fmt::Display for ItemId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum ItemCategory {
+    Consumable,
+    Equipment,
+    Material,
+    Quest,
+    Misc,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ItemDefinition {
+    pub id: ItemId,
+    /// Display name
+    pub name: String,
+    /// Path of the icon image
+    pub icon: String,
+    pub category: ItemCategory,
+    pub max_stack: u32,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Slot and modifiers of equippable items
+    #[serde(default)]
+    pub equipment: Option<EquipmentDefinition>,
+    /// Effect of consumable items, one is consumed per use
+    #[serde(default)]
+    pub on_use: Option<ItemUse>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub enum ItemUse {
+    Heal(u32),
+    /// Id of a status effect applied to the user
+    StatusEffect(String),
+}
+
+impl ItemDefinition {
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+}
+
+#[derive(Asset, TypePath, Deserialize, Debug, Default)]
+#[serde(from = "Vec<ItemDefinition>")]
+pub struct ItemRegistry {
+    items: HashMap<ItemId, ItemDefinition>,
+}
+
+impl From<Vec<ItemDefinition>> for ItemRegistry {
+    fn from(definitions: Vec<ItemDefinition>) -> Self {
+        Self {
+            items: definitions
+                .into_iter()
+                .map(|definition| (definition.id.clone(), definition))
+                .collect(),
+        }
+    }
+}
+
+impl ItemRegistry {
+    pub fn get(&self, id: &ItemId) -> Option<&ItemDefinition> {
+        self.items.get(id)
+    }
 
-use crate::entities::Player;
+    pub fn iter(&self) -> impl Iterator<Item = &ItemDefinition> {
+        self.items.values()
+    }
+}
+
+#[derive(AssetCollection, Resource)]
+pub(crate) struct ItemAssets {
+    #[asset(path = "data/items.items.ron")]
+    pub registry: Handle<ItemRegistry>,
+}
+
+/// System parameter giving access to the loaded [`ItemRegistry`]
+#[derive(SystemParam)]
+pub(crate) struct Items<'w> {
+    assets: Res<'w, ItemAssets>,
+    registries: Res<'w, Assets<ItemRegistry>>,
+}
+
+impl Items<'_> {
+    pub fn registry(&self) -> Option<&ItemRegistry> {
+        self.registries.get(&self.assets.registry)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum InventoryError {
+    #[error("the inventory is full")]
+    Full,
+    #[error("unknown item {0}")]
+    UnknownItem(ItemId),
+    #[error("missing {missing} {item}")]
+    NotEnough {
+        item: ItemId,
+        missing: u32,
+    },
+    #[error("no slot {0}")]
+    InvalidSlot(usize),
+    #[error("{0} can't be equipped")]
+    NotEquipment(ItemId),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ItemStack {
+    pub item: ItemId,
+    pub quantity: u32,
+}
+
+/// Slots of stacked items
+#[derive(Component, Clone, Debug, PartialEq, Eq)]
+pub struct Inventory {
+    pub slots: Vec<Option<ItemStack>>,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self::new(24)
+    }
+}
+
+impl Inventory {
+    pub fn new(slots: usize) -> Self {
+        Self { slots: vec![None; slots] }
+    }
+
+    pub fn count(&self, item: &ItemId) -> u32 {
+        self.stacks()
+            .filter(|stack| &stack.item == item)
+            .map(|stack| stack.quantity)
+            .sum()
+    }
+
+    pub fn stacks(&self) -> impl Iterator<Item = &ItemStack> {
+        self.slots.iter().flatten()
+    }
 
+    pub fn is_empty(&self) -> bool {
+        self.slots.iter().all(Option::is_none)
+    }
+
+    /// Number of `item` that still fits in the inventory
+    pub fn space_for(&self, registry: &ItemRegistry, item: &ItemId) -> Result<u32, InventoryError> {
+        let max_stack = registry
+            .get(item)
+            .ok_or_else(|| InventoryError::UnknownItem(item.clone()))?
+            .max_stack.max(1);
+        Ok(
+            self.slots
+                .iter()
+                .map(|slot| {
+                    match slot {
+                        None => max_stack,
+                        Some(stack) if &stack.item == item =>
+                            max_stack.saturating_sub(stack.quantity),
+                        Some(_) => 0,
+                    }
+                })
+                .sum()
+        )
+    }
+
+    /// Adds `quantity` of `item`, topping up existing stacks first.
+    ///
+    /// Nothing is added if everything doesn't fit.
+    pub fn add(
+        &mut self,
+        registry: &ItemRegistry,
+        item: &ItemId,
+        quantity: u32
+    ) -> Result<(), InventoryError> {
+        if self.space_for(registry, item)? < quantity {
+            return Err(InventoryError::Full);
+        }
+        let max_stack = registry.get(item).map_or(1, |definition| definition.max_stack.max(1));
+        let mut left = quantity;
+        for stack in self.slots.iter_mut().flatten() {
+            if left == 0 {
+                break;
+            }
+            if &stack.item == item {
+                let added = left.min(max_stack.saturating_sub(stack.quantity));
+                stack.quantity += added;
+                left -= added;
+            }
+        }
+        for slot in &mut self.slots {
+            if left == 0 {
+                break;
+            }
+            if slot.is_none() {
+                let added = left.min(max_stack);
+                *slot = Some(ItemStack { item: item.clone(), quantity: added });
+                left -= added;
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes `quantity` of `item`, emptying the last stacks first.
+    ///
+    /// Nothing is removed if there isn't enough.
+    pub fn remove(&mut self, item: &ItemId, quantity: u32) -> Result<(), InventoryError> {
+        let count = self.count(item);
+        if count < quantity {
+            return Err(InventoryError::NotEnough {
+                item: item.clone(),
+                missing: quantity - count,
+            });
+        }
+        let mut left = quantity;
+        for slot in self.slots.iter_mut().rev() {
+            if left == 0 {
+                break;
+            }
+            let Some(stack) = slot else {
+                continue;
+            };
+            if &stack.item != item {
+                continue;
+            }
+            let removed = left.min(stack.quantity);
+            stack.quantity -= removed;
+            left -= removed;
+            if stack.quantity == 0 {
+                *slot = None;
+            }
+        }
+        Ok(())
+    }
+
+    /// Takes the whole stack out of a slot
+    pub fn take_slot(&mut self, slot: usize) -> Result<Option<ItemStack>, InventoryError> {
+        self.slots
+            .get_mut(slot)
+            .map(Option::take)
+            .ok_or(InventoryError::InvalidSlot(slot))
+    }
+
+    /// Moves `quantity` of `item` into another inventory, or nothing on error
+    pub fn transfer(
+        &mut self,
+        other: &mut Inventory,
+        registry: &ItemRegistry,
+        item: &ItemId,
+        quantity: u32
+    ) -> Result<(), InventoryError> {
+        let count = self.count(item);
+        if count < quantity {
+            return Err(InventoryError::NotEnough {
+                item: item.clone(),
+                missing: quantity - count,
+            });
+        }
+        other.add(registry, item, quantity)?;
+        self.remove(item, quantity)
+    }
+
+    /// Moves every stack into another inventory, keeping what doesn't fit
+    pub fn transfer_all(&mut self, other: &mut Inventory, registry: &ItemRegistry) -> Vec<InventoryError> {
+        let mut errors = Vec::new();
+        for slot in &mut self.slots {
+            let Some(stack) = slot else {
+                continue;
+            };
+            match other.add(registry, &stack.item, stack.quantity) {
+                Ok(()) => {
+                    *slot = None;
+                }
+                Err(error) => errors.push(error),
+            }
+        }
+        errors
+    }
+}
+
+/// Raw content of the LDtk `items` enum array field
 #[derive(Clone, Component, Debug, Eq, Default, PartialEq)]
-pub struct Items(Vec<String>);
+pub struct LdtkItems(Vec<String>);
 
-impl From<&EntityInstance> for Items {
+impl From<&EntityInstance> for LdtkItems {
     fn from(entity_instance: &EntityInstance) -> Self {
-        Items(
+        LdtkItems(
             entity_instance
                 .iter_enums_field("items")
                 .expect("items field should be correctly typed")
@@ -21,14 +351,200 @@ impl From<&EntityInstance> for Items {
     }
 }
 
+/// Maps the LDtk `items` field onto registry ids, filling the inventory
+pub(crate) fn fill_inventory_from_ldtk(
+    mut query: Query<(Entity, &LdtkItems, &mut Inventory), Added<LdtkItems>>,
+    items: Items
+) {
+    let Some(registry) = items.registry() else {
+        return;
+    };
+    for (entity, ldtk_items, mut inventory) in &mut query {
+        for ldtk_item in &ldtk_items.0 {
+            let item = ItemId::from(ldtk_item.as_str());
+            if let Err(error) = inventory.add(registry, &item, 1) {
+                log::warn!("Could not add LDtk item {} to {:?}: {}", ldtk_item, entity, error);
+            }
+        }
+    }
+}
+
+/// Request for `user` to consume one `item` of its inventory
+#[derive(Event, Clone, Debug)]
+pub(crate) struct UseItem {
+    pub user: Entity,
+    pub item: ItemId,
+}
+
+pub(crate) fn use_items(
+    mut events: EventReader<UseItem>,
+    mut query: Query<&mut Inventory>,
+    mut heal_events: EventWriter<HealEvent>,
+    mut status_events: EventWriter<ApplyStatusEffect>,
+    items: Items
+) {
+    let Some(registry) = items.registry() else {
+        return;
+    };
+    for UseItem { user, item } in events.read() {
+        let on_use = registry.get(item).and_then(|definition| definition.on_use.as_ref());
+        let Some(on_use) = on_use else {
+            log::warn!("{} can't be used", item);
+            continue;
+        };
+        let Ok(mut inventory) = query.get_mut(*user) else {
+            continue;
+        };
+        if let Err(error) = inventory.remove(item, 1) {
+            log::warn!("{:?} could not use {}: {}", user, item, error);
+            continue;
+        }
+        match on_use {
+            ItemUse::Heal(amount) => {
+                heal_events.send(HealEvent {
+                    target: *user,
+                    amount: *amount,
+                });
+            }
+            ItemUse::StatusEffect(effect) => {
+                status_events.send(ApplyStatusEffect {
+                    target: *user,
+                    effect: effect.clone(),
+                });
+            }
+        }
+    }
+}
+
 pub fn dbg_player_items(
     input: Res<ButtonInput<KeyCode>>,
-    mut query: Query<(&Items, &EntityInstance), With<Player>>
+    mut query: Query<(&Inventory, &EntityInstance), With<Player>>
 ) {
-    for (items, entity_instance) in &mut query {
+    for (inventory, entity_instance) in &mut query {
         if input.just_pressed(KeyCode::KeyP) {
-            dbg!(&items);
+            dbg!(&inventory);
             dbg!(&entity_instance);
         }
     }
 }
+
+pub(crate) fn plugin(app: &mut App) {
+    app.register_ron_asset::<ItemRegistry>(&["items.ron"])
+        .configure_loading_state(
+            LoadingStateConfig::new(GameState::SplashScreen).load_collection::<ItemAssets>()
+        )
+        .add_event::<UseItem>()
+        .add_systems(
+            Update,
+            (fill_inventory_from_ldtk, use_items).run_if(in_state(GameState::Playing))
+        );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> ItemRegistry {
+        let definition = |id: &str, max_stack| ItemDefinition {
+            id: id.into(),
+            name: id.to_owned(),
+            icon: String::new(),
+            category: ItemCategory::Material,
+            max_stack,
+            tags: Vec::new(),
+            equipment: None,
+            on_use: None,
+        };
+        ItemRegistry::from(vec![definition("herb", 10), definition("sword", 1)])
+    }
+
+    fn stack(item: &str, quantity: u32) -> Option<ItemStack> {
+        Some(ItemStack { item: item.into(), quantity })
+    }
+
+    #[test]
+    fn add_tops_up_stacks_before_using_empty_slots() {
+        let registry = registry();
+        let mut inventory = Inventory::new(3);
+        inventory.add(&registry, &"herb".into(), 4).unwrap();
+        inventory.add(&registry, &"herb".into(), 8).unwrap();
+        assert_eq!(inventory.slots, vec![stack("herb", 10), stack("herb", 2), None]);
+        assert_eq!(inventory.count(&"herb".into()), 12);
+    }
+
+    #[test]
+    fn add_nothing_when_it_does_not_fit() {
+        let registry = registry();
+        let mut inventory = Inventory::new(2);
+        inventory.add(&registry, &"sword".into(), 1).unwrap();
+        assert_eq!(inventory.add(&registry, &"herb".into(), 11), Err(InventoryError::Full));
+        assert_eq!(inventory.slots, vec![stack("sword", 1), None]);
+        assert_eq!(
+            inventory.add(&registry, &"stone".into(), 1),
+            Err(InventoryError::UnknownItem("stone".into()))
+        );
+    }
+
+    #[test]
+    fn remove_empties_the_last_stacks_first() {
+        let registry = registry();
+        let mut inventory = Inventory::new(3);
+        inventory.add(&registry, &"herb".into(), 15).unwrap();
+        inventory.remove(&"herb".into(), 7).unwrap();
+        assert_eq!(inventory.slots, vec![stack("herb", 8), None, None]);
+        inventory.remove(&"herb".into(), 8).unwrap();
+        assert!(inventory.is_empty());
+    }
+
+    #[test]
+    fn remove_nothing_when_missing_some() {
+        let registry = registry();
+        let mut inventory = Inventory::new(3);
+        inventory.add(&registry, &"herb".into(), 3).unwrap();
+        assert_eq!(
+            inventory.remove(&"herb".into(), 5),
+            Err(InventoryError::NotEnough { item: "herb".into(), missing: 2 })
+        );
+        assert_eq!(inventory.count(&"herb".into()), 3);
+    }
+
+    #[test]
+    fn transfer_moves_items_between_inventories() {
+        let registry = registry();
+        let mut chest = Inventory::new(2);
+        let mut bag = Inventory::new(2);
+        chest.add(&registry, &"herb".into(), 6).unwrap();
+        chest.transfer(&mut bag, &registry, &"herb".into(), 4).unwrap();
+        assert_eq!(chest.count(&"herb".into()), 2);
+        assert_eq!(bag.count(&"herb".into()), 4);
+        assert_eq!(
+            chest.transfer(&mut bag, &registry, &"herb".into(), 3),
+            Err(InventoryError::NotEnough { item: "herb".into(), missing: 1 })
+        );
+    }
+
+    #[test]
+    fn transfer_nothing_when_the_other_inventory_is_full() {
+        let registry = registry();
+        let mut chest = Inventory::new(1);
+        let mut bag = Inventory::new(1);
+        chest.add(&registry, &"herb".into(), 5).unwrap();
+        bag.add(&registry, &"sword".into(), 1).unwrap();
+        assert_eq!(chest.transfer(&mut bag, &registry, &"herb".into(), 5), Err(InventoryError::Full));
+        assert_eq!(chest.count(&"herb".into()), 5);
+        assert_eq!(bag.slots, vec![stack("sword", 1)]);
+    }
+
+    #[test]
+    fn transfer_all_keeps_what_does_not_fit() {
+        let registry = registry();
+        let mut chest = Inventory::new(2);
+        let mut bag = Inventory::new(1);
+        chest.add(&registry, &"herb".into(), 5).unwrap();
+        chest.add(&registry, &"sword".into(), 1).unwrap();
+        let errors = chest.transfer_all(&mut bag, &registry);
+        assert_eq!(errors, vec![InventoryError::Full]);
+        assert_eq!(chest.slots, vec![None, stack("sword", 1)]);
+        assert_eq!(bag.slots, vec![stack("herb", 5)]);
+    }
+}
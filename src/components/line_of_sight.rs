@@ -2,93 +2,296 @@ use std::marker::PhantomData;
 
 use bevy::{
     core::Name,
-    ecs::{ component::Component, entity::Entity, query::With, system::{ Query, Res } },
+    ecs::{ component::Component, entity::Entity, query::QueryFilter, system::{ Query, Res } },
     log,
     math::Vec2,
+    time::Time,
     transform::components::GlobalTransform,
+    utils::HashMap,
 };
-use bevy_rapier2d::{ pipeline::QueryFilter, plugin::RapierContext };
+use bevy_rapier2d::{ pipeline::QueryFilter as RapierQueryFilter, plugin::RapierContext };
 
-/// Component applied to entities that should detect line of sight to the target
+use super::facing::Facing;
+
+/// Component applied to entities that should detect line of sight to targets.
+///
+/// `Targets` is a query filter selecting the candidates, like `With<Player>` or
+/// `Or<(With<Player>, With<Pet>)>`. Targets are seen inside a cone around the
+/// [`Facing`] of the observer: far away in the focused center of the cone, and
+/// closer in its periphery. Seeing a target fills its awareness meter, the
+/// target is detected once it's full, and the most noticed candidate is the
+/// tracked one.
 #[derive(Component, Clone)]
-pub(crate) struct LineOfSight<Target> {
-    max_distance: f32,
+pub(crate) struct LineOfSight<Targets> {
+    /// Angle of the whole cone, in radians
+    fov: f32,
+    /// Angle of the focused center of the cone, in radians
+    focus: f32,
+    focused_range: f32,
+    peripheral_range: f32,
+    /// Awareness gained per second looking straight at a target
+    fill_rate: f32,
+    /// Awareness lost per second once out of sight
+    decay_rate: f32,
+    awareness: HashMap<Entity, f32>,
+    tracked: Option<Entity>,
     last_sighted: Option<Vec2>,
     in_sight: bool,
-    marker: PhantomData<Target>,
+    marker: PhantomData<Targets>,
 }
 
-/// Component applied to entities that seek the target
-impl<Target> Default for LineOfSight<Target> {
+impl<Targets> Default for LineOfSight<Targets> {
     fn default() -> Self {
-        Self { max_distance: 250.0, last_sighted: None, in_sight: false, marker: PhantomData }
+        Self {
+            fov: (120.0_f32).to_radians(),
+            focus: (40.0_f32).to_radians(),
+            focused_range: 250.0,
+            peripheral_range: 120.0,
+            fill_rate: 1.5,
+            decay_rate: 0.5,
+            awareness: HashMap::new(),
+            tracked: None,
+            last_sighted: None,
+            in_sight: false,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<Targets> LineOfSight<Targets> {
+    pub fn with_cone(mut self, fov: f32, focus: f32) -> Self {
+        self.fov = fov;
+        self.focus = focus.min(fov);
+        self
+    }
+
+    pub fn with_ranges(mut self, focused: f32, peripheral: f32) -> Self {
+        self.focused_range = focused;
+        self.peripheral_range = peripheral;
+        self
+    }
+
+    /// Whether the tracked target is detected and currently seen
+    pub fn in_sight(&self) -> bool {
+        self.in_sight
+    }
+
+    /// The candidate the observer pays the most attention to
+    pub fn tracked(&self) -> Option<Entity> {
+        self.tracked
+    }
+
+    /// Awareness of the tracked target, from 0 to 1
+    pub fn awareness(&self) -> f32 {
+        self.tracked.and_then(|target| self.awareness.get(&target).copied()).unwrap_or(0.0)
+    }
+
+    /// Global position where the tracked target was last seen
+    pub fn last_sighted(&self) -> Option<Vec2> {
+        self.last_sighted
+    }
+
+    /// How well a target at `vector` from the observer is seen, from 0 (not
+    /// in the cone) to 1 (close and in focus)
+    fn visibility(&self, facing: Vec2, vector: Vec2) -> f32 {
+        let distance = vector.length();
+        if distance <= f32::EPSILON {
+            return 1.0;
+        }
+        let angle = facing.angle_between(vector).abs();
+        let (range, focus) = if angle <= self.focus / 2.0 {
+            (self.focused_range, 1.0)
+        } else if angle <= self.fov / 2.0 {
+            (self.peripheral_range, 0.5)
+        } else {
+            return 0.0;
+        };
+        if distance > range {
+            return 0.0;
+        }
+        focus * (1.0 - (distance / range) * 0.5)
+    }
+
+    /// Fills the awareness of the `seen` targets by how visible they are, lets
+    /// it fade for the others, and tracks the most noticed one. Returns whether
+    /// the tracked target came into sight, or out of it.
+    fn update_awareness(&mut self, seen: &HashMap<Entity, (f32, Vec2)>, delta: f32) -> Option<bool> {
+        let (fill_rate, decay_rate) = (self.fill_rate, self.decay_rate);
+        for (entity, (visibility, _)) in seen {
+            let value = self.awareness.entry(*entity).or_insert(0.0);
+            *value = (*value + fill_rate * visibility * delta).min(1.0);
+        }
+        self.awareness.retain(|entity, value| {
+            if !seen.contains_key(entity) {
+                *value -= decay_rate * delta;
+            }
+            *value > 0.0
+        });
+        self.tracked = self.awareness
+            .iter()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(entity, _)| *entity);
+        let detected = self.tracked
+            .filter(|entity| self.awareness.get(entity).is_some_and(|value| *value >= 1.0))
+            .and_then(|entity| seen.get(&entity));
+
+        let was_in_sight = self.in_sight;
+        self.in_sight = detected.is_some();
+        if let Some((_, target_pos)) = detected {
+            self.last_sighted = Some(*target_pos);
+        }
+        (self.in_sight != was_in_sight).then_some(self.in_sight)
     }
 }
 
-pub(crate) fn line_of_sight<Target: Component>(
-    targets: Query<(Entity, &GlobalTransform), With<Target>>,
-    mut observers: Query<(&mut LineOfSight<Target>, &GlobalTransform, Entity, Option<&Name>)>,
+pub(crate) fn line_of_sight<Targets: QueryFilter + Send + Sync + 'static>(
+    time: Res<Time>,
+    targets: Query<(Entity, &GlobalTransform), Targets>,
+    mut observers: Query<
+        (&mut LineOfSight<Targets>, &GlobalTransform, Entity, Option<&Facing>, Option<&Name>)
+    >,
     rapier_context: Res<RapierContext>
 ) {
-    for (target_entity, target_transform) in &targets {
-        let target_pos = target_transform.translation().truncate();
-
-        // Iterate over entities having a LineOfSight component
-        for (
-            mut line_of_sight,
-            observer_transform,
-            observer_entity,
-            observer_name,
-        ) in &mut observers {
-            let observer_pos = observer_transform.translation().truncate();
+    let delta = time.delta_seconds();
+    // Iterate over entities having a LineOfSight component
+    for (
+        mut line_of_sight,
+        observer_transform,
+        observer_entity,
+        facing,
+        observer_name,
+    ) in &mut observers {
+        let observer_pos = observer_transform.translation().truncate();
+        let facing = facing.map_or(Vec2::X, |facing| facing.0);
+        let mut seen = HashMap::new();
+
+        for (target_entity, target_transform) in &targets {
+            if target_entity == observer_entity {
+                continue;
+            }
+            let target_pos = target_transform.translation().truncate();
             let vector = target_pos - observer_pos;
-            let max_distance = line_of_sight.max_distance;
-            let distance = vector.length();
-            if distance > max_distance {
+            let visibility = line_of_sight.visibility(facing, vector);
+            if visibility <= 0.0 {
                 continue;
             }
-            let ray_dir = vector.normalize();
 
             // Cast ray from the observer toward the target
-            let stalked = if
-                let Some((collided_entity, toi)) = rapier_context.cast_ray(
+            let visible = rapier_context
+                .cast_ray(
                     observer_pos,
-                    ray_dir,
-                    max_distance,
+                    vector.normalize(),
+                    vector.length(),
                     false,
                     // FIXME: make sight obstacles (wall etc) into a group and use .groups() to only hit target and obstacles
-                    QueryFilter::new().exclude_sensors().exclude_collider(observer_entity)
+                    RapierQueryFilter::new().exclude_sensors().exclude_collider(observer_entity)
                 )
-            {
-                // TODO: remove debug
-                if collided_entity == target_entity && !line_of_sight.in_sight {
-                    log::debug!(
-                        "{:?}({:?}) sees the target! toi={}",
-                        observer_name,
-                        observer_entity,
-                        toi
-                    );
-                }
-                collided_entity == target_entity
-            } else {
-                false
-            };
-
-            // Minimal updates
-            if stalked {
-                let last_sighted = Some(target_pos);
-                if !line_of_sight.in_sight || line_of_sight.last_sighted != last_sighted {
-                    let los_mut = line_of_sight.as_mut();
-                    los_mut.last_sighted = last_sighted;
-                    los_mut.in_sight = true;
-                }
-            } else {
-                if line_of_sight.in_sight {
-                    line_of_sight.in_sight = false;
-                    // TODO: remove debug
-                    log::debug!("{:?}({:?}) lost the target.", observer_name, observer_entity);
-                }
+                .is_some_and(|(collided_entity, _)| collided_entity == target_entity);
+            if visible {
+                seen.insert(target_entity, (visibility, target_pos));
             }
         }
+
+        // Minimal updates: nothing to fill, fade or lose
+        if seen.is_empty() && line_of_sight.awareness.is_empty() && !line_of_sight.in_sight {
+            continue;
+        }
+        let tracked = line_of_sight.tracked;
+        let sighting = line_of_sight.update_awareness(&seen, delta);
+        if line_of_sight.tracked != tracked {
+            log::trace!(
+                "{:?}({:?}) now tracks {:?} (awareness {})",
+                observer_name,
+                observer_entity,
+                line_of_sight.tracked,
+                line_of_sight.awareness()
+            );
+        }
+        match sighting {
+            Some(true) =>
+                log::trace!(
+                    "{:?}({:?}) sees the target {:?}!",
+                    observer_name,
+                    observer_entity,
+                    line_of_sight.tracked
+                ),
+            Some(false) =>
+                log::trace!("{:?}({:?}) lost the target.", observer_name, observer_entity),
+            None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn direction(degrees: f32) -> Vec2 {
+        Vec2::from_angle(degrees.to_radians())
+    }
+
+    fn seen(targets: &[(Entity, f32)]) -> HashMap<Entity, (f32, Vec2)> {
+        targets
+            .iter()
+            .map(|(entity, visibility)| (*entity, (*visibility, Vec2::new(10.0, 0.0))))
+            .collect()
+    }
+
+    #[test]
+    fn focused_targets_are_seen_further() {
+        let line_of_sight = LineOfSight::<()>::default();
+        // Straight ahead, in focus up to 250
+        assert_eq!(line_of_sight.visibility(Vec2::X, Vec2::new(125.0, 0.0)), 0.75);
+        assert_eq!(line_of_sight.visibility(Vec2::X, Vec2::new(260.0, 0.0)), 0.0);
+        // In the periphery, up to 120 and half as well
+        let peripheral = line_of_sight.visibility(Vec2::X, direction(50.0) * 60.0);
+        assert!((peripheral - 0.375).abs() < 1e-5);
+        assert_eq!(line_of_sight.visibility(Vec2::X, direction(50.0) * 150.0), 0.0);
+    }
+
+    #[test]
+    fn targets_out_of_the_cone_are_not_seen() {
+        let line_of_sight = LineOfSight::<()>::default().with_cone((90.0_f32).to_radians(), 0.0);
+        assert_eq!(line_of_sight.visibility(Vec2::X, direction(50.0) * 10.0), 0.0);
+        assert_eq!(line_of_sight.visibility(Vec2::NEG_X, Vec2::new(10.0, 0.0)), 0.0);
+        // Facing left now
+        assert!(line_of_sight.visibility(Vec2::NEG_X, Vec2::new(-10.0, 0.0)) > 0.0);
+        assert_eq!(line_of_sight.visibility(Vec2::X, Vec2::ZERO), 1.0);
+    }
+
+    #[test]
+    fn awareness_fills_until_detection() {
+        let target = Entity::from_raw(1);
+        let mut line_of_sight = LineOfSight::<()>::default();
+        assert_eq!(line_of_sight.update_awareness(&seen(&[(target, 1.0)]), 0.5), None);
+        assert_eq!(line_of_sight.tracked(), Some(target));
+        assert_eq!(line_of_sight.awareness(), 0.75);
+        assert!(!line_of_sight.in_sight());
+        assert_eq!(line_of_sight.update_awareness(&seen(&[(target, 1.0)]), 0.5), Some(true));
+        assert_eq!(line_of_sight.awareness(), 1.0);
+        assert_eq!(line_of_sight.last_sighted(), Some(Vec2::new(10.0, 0.0)));
+    }
+
+    #[test]
+    fn awareness_decays_out_of_sight() {
+        let target = Entity::from_raw(1);
+        let mut line_of_sight = LineOfSight::<()>::default();
+        line_of_sight.update_awareness(&seen(&[(target, 1.0)]), 1.0);
+        assert!(line_of_sight.in_sight());
+        assert_eq!(line_of_sight.update_awareness(&HashMap::new(), 0.5), Some(false));
+        assert_eq!(line_of_sight.awareness(), 0.75);
+        // Still remembered where it was
+        assert_eq!(line_of_sight.last_sighted(), Some(Vec2::new(10.0, 0.0)));
+        line_of_sight.update_awareness(&HashMap::new(), 2.0);
+        assert_eq!(line_of_sight.tracked(), None);
+        assert_eq!(line_of_sight.awareness(), 0.0);
+    }
+
+    #[test]
+    fn the_most_noticed_target_is_tracked() {
+        let (far, close) = (Entity::from_raw(1), Entity::from_raw(2));
+        let mut line_of_sight = LineOfSight::<()>::default();
+        line_of_sight.update_awareness(&seen(&[(far, 0.2), (close, 0.6)]), 0.5);
+        assert_eq!(line_of_sight.tracked(), Some(close));
     }
 }
@@ -0,0 +1,286 @@
+//! Loot tables rolled when an enemy dies or a container is opened.
+//!
+//! Tables are defined in `data/loot_tables.loot.ron`:
+//!
+//! ```ron
+//! {
+//!     "skeleton": (
+//!         rolls: 2,
+//!         entries: [
+//!             (item: Some("Bone"), weight: 5, quantity: (1, 3)),
+//!             (item: Some("Gold"), weight: 2, quantity: (5, 10), conditions: [KilledByPlayer]),
+//!             (item: None, weight: 3),
+//!         ],
+//!     ),
+//! }
+//! ```
+//!
+//! Rolls draw from a [`ForkedEntropy`] of the seeded global entropy, the same
+//! seed always gives the same loot.
+
+use bevy::{ prelude::*, utils::HashMap };
+use bevy_asset_loader::{
+    asset_collection::AssetCollection,
+    loading_state::{ config::{ ConfigureLoadingState, LoadingStateConfig }, LoadingStateAppExt },
+};
+use bevy_ecs_ldtk::{ prelude::LdtkFields, EntityInstance };
+use rand::{ seq::SliceRandom, Rng };
+use serde::Deserialize;
+
+use super::{ health::Died, items::ItemId, pickup::SpawnPickup };
+use crate::{
+    entities::Player,
+    plugins::{
+        entropy::{ EntropyAppExt, ForkedEntropy },
+        gamestate::GameState,
+        ron_asset::RonAssetAppExt,
+    },
+};
+
+/// Where the loot comes from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum LootSource {
+    Death {
+        killed_by_player: bool,
+    },
+    Container,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub(crate) enum LootCondition {
+    KilledByPlayer,
+    FromDeath,
+    FromContainer,
+    /// Probability for the entry to actually drop once picked
+    Chance(f32),
+}
+
+impl LootCondition {
+    fn is_met(&self, source: LootSource) -> bool {
+        match (self, source) {
+            (Self::KilledByPlayer, LootSource::Death { killed_by_player }) => killed_by_player,
+            (Self::FromDeath, LootSource::Death { .. }) => true,
+            (Self::FromContainer, LootSource::Container) => true,
+            (Self::Chance(_), _) => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct LootEntry {
+    /// Dropped item id, `None` for an empty roll
+    pub item: Option<ItemId>,
+    pub weight: u32,
+    /// Inclusive quantity range
+    #[serde(default = "single")]
+    pub quantity: (u32, u32),
+    #[serde(default)]
+    pub conditions: Vec<LootCondition>,
+}
+
+fn single() -> (u32, u32) {
+    (1, 1)
+}
+
+fn one() -> u32 {
+    1
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct LootTable {
+    /// Number of weighted picks
+    #[serde(default = "one")]
+    pub rolls: u32,
+    pub entries: Vec<LootEntry>,
+}
+
+impl LootTable {
+    /// Rolls the table, returning the dropped item ids and quantities
+    pub fn roll<R: Rng + ?Sized>(&self, rng: &mut R, source: LootSource) -> Vec<(ItemId, u32)> {
+        let entries = self.entries
+            .iter()
+            .filter(|entry| entry.conditions.iter().all(|condition| condition.is_met(source)))
+            .collect::<Vec<_>>();
+        let mut drops = Vec::new();
+        for _ in 0..self.rolls {
+            let Ok(entry) = entries.choose_weighted(rng, |entry| entry.weight) else {
+                break;
+            };
+            let chance = entry.conditions.iter().all(|condition| {
+                match condition {
+                    LootCondition::Chance(probability) => rng.gen_bool(probability.clamp(0.0, 1.0).into()),
+                    _ => true,
+                }
+            });
+            let (Some(item), true) = (&entry.item, chance) else {
+                continue;
+            };
+            let (min, max) = entry.quantity;
+            let quantity = rng.gen_range(min..=max.max(min));
+            if quantity > 0 {
+                drops.push((item.clone(), quantity));
+            }
+        }
+        drops
+    }
+}
+
+#[derive(Asset, TypePath, Deserialize, Debug, Default)]
+#[serde(transparent)]
+pub(crate) struct LootTables(pub HashMap<String, LootTable>);
+
+#[derive(AssetCollection, Resource)]
+pub(crate) struct LootAssets {
+    #[asset(path = "data/loot_tables.loot.ron")]
+    pub tables: Handle<LootTables>,
+}
+
+/// The loot table of an entity, from the `loot_table` LDtk field
+#[derive(Component, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Loot {
+    pub table: String,
+}
+
+/// Request to roll the loot table `table` and spawn the drops at `position`
+#[derive(Event, Clone, Debug)]
+pub(crate) struct DropLoot {
+    pub table: String,
+    pub position: Vec2,
+    pub source: LootSource,
+}
+
+pub(crate) fn insert_loot_tables(
+    mut commands: Commands,
+    query: Query<(Entity, &EntityInstance), Added<EntityInstance>>
+) {
+    for (entity, entity_instance) in &query {
+        if let Ok(table) = entity_instance.get_string_field("loot_table") {
+            commands.entity(entity).insert(Loot { table: table.clone() });
+        }
+    }
+}
+
+pub(crate) fn drop_loot_on_death(
+    mut died_events: EventReader<Died>,
+    mut drop_events: EventWriter<DropLoot>,
+    query: Query<(&Loot, &GlobalTransform)>,
+    players: Query<(), With<Player>>
+) {
+    for Died { entity, killer } in died_events.read() {
+        let Ok((loot, transform)) = query.get(*entity) else {
+            continue;
+        };
+        drop_events.send(DropLoot {
+            table: loot.table.clone(),
+            position: transform.translation().truncate(),
+            source: LootSource::Death {
+                killed_by_player: killer.is_some_and(|killer| players.contains(killer)),
+            },
+        });
+    }
+}
+
+pub(crate) fn roll_loot(
+    mut drop_events: EventReader<DropLoot>,
+    mut spawn_events: EventWriter<SpawnPickup>,
+    mut rng: ResMut<ForkedEntropy<DropLoot>>,
+    assets: Res<LootAssets>,
+    loot_tables: Res<Assets<LootTables>>
+) {
+    let Some(loot_tables) = loot_tables.get(&assets.tables) else {
+        return;
+    };
+    for DropLoot { table, position, source } in drop_events.read() {
+        let Some(loot_table) = loot_tables.0.get(table) else {
+            warn!("Unknown loot table {:?}", table);
+            continue;
+        };
+        for (item, quantity) in loot_table.roll(rng.as_mut(), *source) {
+            let velocity = Vec2::new(rng.gen_range(-60.0..60.0), 150.0);
+            spawn_events.send(SpawnPickup {
+                item,
+                quantity,
+                position: *position,
+                velocity,
+                dropped_by: None,
+            });
+        }
+    }
+}
+
+pub(crate) fn plugin(app: &mut App) {
+    app.register_ron_asset::<LootTables>(&["loot.ron"])
+        .add_event::<DropLoot>()
+        .init_forked_entropy::<DropLoot>()
+        .configure_loading_state(
+            LoadingStateConfig::new(GameState::SplashScreen).load_collection::<LootAssets>()
+        )
+        .add_systems(
+            Update,
+            (insert_loot_tables, drop_loot_on_death, roll_loot)
+                .chain()
+                .after(super::health::apply_damage)
+                .before(super::pickup::spawn_pickups)
+                .run_if(in_state(GameState::Playing))
+        );
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_rand::prelude::{ EntropyPlugin, WyRand };
+    use rand::{ RngCore, SeedableRng };
+
+    use super::*;
+
+    fn table() -> LootTable {
+        ron::de::from_str(
+            r#"(
+                rolls: 4,
+                entries: [
+                    (item: Some("Bone"), weight: 5, quantity: (1, 3)),
+                    (item: Some("Gold"), weight: 2, quantity: (5, 10), conditions: [KilledByPlayer]),
+                    (item: Some("Gem"), weight: 1, conditions: [Chance(0.5)]),
+                    (item: None, weight: 3),
+                ],
+            )"#
+        ).unwrap()
+    }
+
+    /// The rng a [`roll_loot`] system draws from, forked from a seeded app
+    fn forked_rng(seed: u64) -> ForkedEntropy<DropLoot> {
+        let mut app = App::new();
+        app.add_plugins(EntropyPlugin::<WyRand>::with_seed(seed.to_ne_bytes()))
+            .init_forked_entropy::<DropLoot>();
+        app.world.remove_resource::<ForkedEntropy<DropLoot>>().unwrap()
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_loot() {
+        let table = table();
+        let source = LootSource::Death { killed_by_player: true };
+        let rolls = |rng: &mut dyn RngCore| (0..20).map(|_| table.roll(rng, source)).collect::<Vec<_>>();
+
+        let first = rolls(&mut WyRand::seed_from_u64(1990));
+        assert_eq!(first, rolls(&mut WyRand::seed_from_u64(1990)));
+        assert!(first.iter().any(|drops| !drops.is_empty()));
+
+        assert_eq!(rolls(&mut forked_rng(1990)), rolls(&mut forked_rng(1990)));
+    }
+
+    #[test]
+    fn rolls_only_the_entries_meeting_their_conditions() {
+        let table = table();
+        let mut rng = WyRand::seed_from_u64(1990);
+        for _ in 0..50 {
+            let drops = table.roll(&mut rng, LootSource::Container);
+            assert!(drops.len() <= 4);
+            for (item, quantity) in drops {
+                assert_ne!(item, ItemId::from("Gold"));
+                if item == ItemId::from("Bone") {
+                    assert!((1..=3).contains(&quantity));
+                }
+            }
+        }
+    }
+}
@@ -12,6 +12,18 @@ pub(crate) mod animation;
 pub(crate) mod settings;
 pub(crate) mod interactions;
 pub(crate) mod deathzone;
+pub(crate) mod status_effects;
+pub(crate) mod facing;
+pub(crate) mod projectile;
+pub(crate) mod hitbox;
+pub(crate) mod shapeshift;
+pub(crate) mod pickup;
+pub(crate) mod loot;
+pub(crate) mod equipment;
+pub(crate) mod crafting;
+pub(crate) mod hearing;
+pub(crate) mod pet;
+pub(crate) mod schedule;
 pub(super) mod swimming;
 pub(super) mod climbing;
 pub(super) mod line_of_sight;
@@ -0,0 +1,354 @@
+//! Animals befriended by the player.
+//!
+//! Interacting with a [`Dog`] or a [`Cat`] makes it a [`Pet`] of the player. It
+//! leaves its level to follow the player everywhere, like a
+//! [`Worldly`] entity, and idles next to them. [`Action::CommandPet`] cycles
+//! its [`PetCommand`], which it may or may not obey depending on the
+//! [`Personality`] of its species. Its thinker is dropped then, pets only
+//! follow their commands.
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::{ assets::LdtkProject, EntityInstance, Worldly };
+use bevy_yarnspinner::prelude::DialogueRunner;
+use big_brain::thinker::{ HasThinker, ThinkerBuilder };
+use input_manager::action_state::ActionState;
+use rand::Rng;
+
+use super::{
+    ai::{ fatigue::Asleep, movement::{ Busy, MoveStatus, MoveTo } },
+    animals::{ name_animals, Animal, AnimalType },
+    interactions::{ InteractionSensor, Interactive },
+    items::{ Inventory, ItemId, Items },
+    pickup::{ Pickup, SpawnPickup },
+    predefinedpath::PatrolSuspended,
+    shapeshift::Shapeshifted,
+};
+use crate::{
+    entities::{ cat::Cat, dog::Dog, Player },
+    plugins::{
+        dialogueview::not_in_dialogue,
+        entropy::{ EntropyAppExt, ForkedEntropy },
+        gamestate::GameState,
+        input::Action,
+    },
+};
+
+/// Beyond this distance from its owner, a pet catches up by teleporting
+const TELEPORT_DISTANCE: f32 = 400.0;
+/// How far from itself a pet looks for something to fetch
+const FETCH_RADIUS: f32 = 160.0;
+/// Distance at which an item is grabbed
+const GRAB_DISTANCE: f32 = 12.0;
+/// How far the owner moves before the pet heads for their new position
+const REFOLLOW_DISTANCE: f32 = 16.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum PetCommand {
+    #[default]
+    Follow,
+    Stay,
+    /// Bring back the closest item lying around
+    Fetch,
+}
+
+impl PetCommand {
+    fn next(self, personality: &Personality) -> Self {
+        match self {
+            Self::Follow => Self::Stay,
+            Self::Stay if personality.fetches => Self::Fetch,
+            Self::Stay | Self::Fetch => Self::Follow,
+        }
+    }
+}
+
+/// Animal befriended by `owner`
+#[derive(Component, Clone, Debug)]
+pub struct Pet {
+    pub owner: Entity,
+    pub command: PetCommand,
+    /// Item brought back to the owner
+    pub carrying: Option<(ItemId, u32)>,
+}
+
+/// How an animal behaves as a pet, by species
+#[derive(Component, Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct Personality {
+    /// Distance kept from the owner
+    pub follow_distance: f32,
+    pub speed: f32,
+    /// Chance to obey a command, from 0 to 1
+    pub obedience: f32,
+    pub fetches: bool,
+}
+
+impl Default for Personality {
+    fn default() -> Self {
+        Self {
+            follow_distance: 40.0,
+            speed: 60.0,
+            obedience: 0.8,
+            fetches: false,
+        }
+    }
+}
+
+impl Personality {
+    pub fn of<A: Animal>() -> Self {
+        match A::animal_type() {
+            // Eager, stays close and plays fetch
+            AnimalType::Dog =>
+                Self {
+                    follow_distance: 28.0,
+                    speed: 90.0,
+                    obedience: 0.95,
+                    fetches: true,
+                },
+            // Keeps its distance, and does what it wants half the time
+            AnimalType::Cat =>
+                Self {
+                    follow_distance: 64.0,
+                    speed: 70.0,
+                    obedience: 0.5,
+                    fetches: false,
+                },
+        }
+    }
+}
+
+/// Animal that can be befriended by interacting with it
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct Befriendable;
+
+pub(crate) fn make_befriendable<A: Animal>(
+    mut commands: Commands,
+    animals: Query<(Entity, &A), Added<A>>
+) {
+    for (entity, animal) in &animals {
+        let name = match animal.name().is_empty() {
+            true => A::species().to_string(),
+            false => animal.name().clone(),
+        };
+        commands.entity(entity).insert((Befriendable, Interactive { name }));
+    }
+}
+
+/// Dialogue greeting a new pet, through the variables set by
+/// [`expose_animal_to_dialogue`](super::animals::expose_animal_to_dialogue)
+const BEFRIENDED_NODE: &str = "Befriended";
+
+/// Befriends the animal closest to a player pressing [`Action::Interact`]
+pub(crate) fn befriend_animals<A: Animal>(
+    mut commands: Commands,
+    sensors: Query<(&InteractionSensor, &Parent)>,
+    players: Query<&ActionState<Action>, With<Player>>,
+    animals: Query<
+        (Option<&EntityInstance>, Option<&HasThinker>),
+        (With<A>, With<Befriendable>, Without<Pet>)
+    >,
+    worlds: Query<Entity, With<Handle<LdtkProject>>>,
+    mut dialogue_runners: Query<&mut DialogueRunner>
+) {
+    for (sensor, parent) in &sensors {
+        let Ok(action_state) = players.get(**parent) else {
+            continue;
+        };
+        if !action_state.just_pressed(&Action::Interact) {
+            continue;
+        }
+        let Some((animal, (entity_instance, has_thinker))) = sensor.closest_entity.and_then(|entity|
+            animals.get(entity).ok().map(|components| (entity, components))
+        ) else {
+            continue;
+        };
+        info!("A {} joins the party", A::species());
+        // Its needs no longer drive it, along with whatever it was doing
+        if let Some(has_thinker) = has_thinker {
+            commands.entity(has_thinker.entity()).despawn_recursive();
+        }
+        let mut entity_commands = commands.entity(animal);
+        entity_commands.remove::<(ThinkerBuilder, HasThinker, Busy, MoveTo, Asleep)>();
+        entity_commands.insert((
+            Pet {
+                owner: **parent,
+                command: PetCommand::Follow,
+                carrying: None,
+            },
+            Personality::of::<A>(),
+            PatrolSuspended,
+        ));
+        // Outlive the level, like the player does
+        if let Some(entity_instance) = entity_instance {
+            entity_commands.insert(Worldly::from_entity_info(entity_instance));
+        }
+        if let Ok(world) = worlds.get_single() {
+            entity_commands.set_parent_in_place(world);
+        }
+        if let Ok(mut dialogue_runner) = dialogue_runners.get_single_mut() {
+            if let Err(error) = dialogue_runner.try_start_node(BEFRIENDED_NODE) {
+                warn!("Could not start the dialogue {:?}: {}", BEFRIENDED_NODE, error);
+            }
+        }
+    }
+}
+
+pub(crate) fn command_pets(
+    players: Query<(Entity, &ActionState<Action>), With<Player>>,
+    mut pets: Query<(&mut Pet, &Personality)>,
+    mut rng: ResMut<ForkedEntropy<PetCommand>>
+) {
+    for (player, action_state) in &players {
+        if !action_state.just_pressed(&Action::CommandPet) {
+            continue;
+        }
+        for (mut pet, personality) in &mut pets {
+            if pet.owner != player {
+                continue;
+            }
+            if rng.gen::<f32>() > personality.obedience {
+                debug!("The pet ignores the command");
+                continue;
+            }
+            pet.command = pet.command.next(personality);
+            debug!("Pet command: {:?}", pet.command);
+        }
+    }
+}
+
+pub(crate) fn pet_behaviour(
+    mut commands: Commands,
+    mut pets: Query<
+        (Entity, &GlobalTransform, &mut Transform, &mut Pet, &Personality, Option<&MoveTo>),
+        (Without<Asleep>, Without<Shapeshifted>)
+    >,
+    mut owners: Query<(&GlobalTransform, Option<&mut Inventory>), Without<Pet>>,
+    pickups: Query<(Entity, &GlobalTransform, &Pickup)>,
+    mut spawn_pickups: EventWriter<SpawnPickup>,
+    items: Items
+) {
+    for (entity, transform, mut local_transform, mut pet, personality, move_to) in &mut pets {
+        let Ok((owner_transform, inventory)) = owners.get_mut(pet.owner) else {
+            debug!("{:?} lost its owner", entity);
+            commands.entity(entity).remove::<(Pet, MoveTo)>();
+            continue;
+        };
+        let position = transform.translation().truncate();
+        let owner_position = owner_transform.translation().truncate();
+        let goal = match (pet.command, &pet.carrying) {
+            (PetCommand::Stay, _) => None,
+            (PetCommand::Follow, _) | (PetCommand::Fetch, Some(_)) => Some(owner_position),
+            (PetCommand::Fetch, None) => {
+                let closest = pickups
+                    .iter()
+                    .map(|(pickup, pickup_transform, item)| {
+                        (pickup, pickup_transform.translation().truncate(), item)
+                    })
+                    .filter(|(_, pickup_position, _)| pickup_position.distance(position) <= FETCH_RADIUS)
+                    .min_by(|(_, a, _), (_, b, _)| {
+                        a.distance_squared(position).total_cmp(&b.distance_squared(position))
+                    });
+                let Some((pickup, pickup_position, item)) = closest else {
+                    debug!("Nothing to fetch");
+                    pet.command = PetCommand::Follow;
+                    continue;
+                };
+                if pickup_position.distance(position) <= GRAB_DISTANCE {
+                    pet.carrying = Some((item.item.clone(), item.quantity));
+                    commands.entity(pickup).despawn_recursive();
+                    continue;
+                }
+                Some(pickup_position)
+            }
+        };
+
+        let Some(goal) = goal else {
+            if move_to.is_some() {
+                commands.entity(entity).remove::<MoveTo>();
+            }
+            continue;
+        };
+        let distance = goal.distance(position);
+        let blocked = move_to.is_some_and(|move_to| move_to.status == MoveStatus::Blocked);
+        if goal == owner_position && (distance > TELEPORT_DISTANCE || (blocked && distance > personality.follow_distance * 4.0)) {
+            // Catch up with the owner, in another level or out of reach
+            local_transform.translation.x = owner_position.x;
+            local_transform.translation.y = owner_position.y;
+            commands.entity(entity).remove::<MoveTo>();
+            continue;
+        }
+        if goal == owner_position && distance <= personality.follow_distance {
+            if let Some((item, quantity)) = pet.carrying.take() {
+                let given = match (inventory, items.registry()) {
+                    (Some(mut inventory), Some(registry)) => inventory.add(registry, &item, quantity).is_ok(),
+                    _ => false,
+                };
+                if !given {
+                    spawn_pickups.send(SpawnPickup {
+                        item,
+                        quantity,
+                        position: owner_position,
+                        velocity: Vec2::ZERO,
+                        dropped_by: None,
+                    });
+                }
+                pet.command = PetCommand::Follow;
+            }
+            // Idle next to the owner
+            if move_to.is_some() {
+                commands.entity(entity).remove::<MoveTo>();
+            }
+            continue;
+        }
+        if move_to.is_some_and(|move_to| move_to.target.distance(goal) <= REFOLLOW_DISTANCE) {
+            continue;
+        }
+        let tolerance = match goal == owner_position {
+            true => personality.follow_distance / 2.0,
+            false => GRAB_DISTANCE / 2.0,
+        };
+        commands.entity(entity).insert(MoveTo::new(goal, personality.speed).with_tolerance(tolerance));
+    }
+}
+
+pub(crate) fn plugin(app: &mut App) {
+    app.register_type::<Personality>().init_forked_entropy::<PetCommand>().add_systems(
+        Update,
+        (
+            (make_befriendable::<Dog>, make_befriendable::<Cat>)
+                .after(name_animals::<Dog>)
+                .after(name_animals::<Cat>),
+            (befriend_animals::<Dog>, befriend_animals::<Cat>).run_if(not_in_dialogue),
+            command_pets.run_if(not_in_dialogue),
+            pet_behaviour,
+        )
+            .chain()
+            .run_if(in_state(GameState::Playing))
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_cycle_through_fetch_for_fetchers() {
+        let dog = Personality::of::<Dog>();
+        assert_eq!(PetCommand::Follow.next(&dog), PetCommand::Stay);
+        assert_eq!(PetCommand::Stay.next(&dog), PetCommand::Fetch);
+        assert_eq!(PetCommand::Fetch.next(&dog), PetCommand::Follow);
+    }
+
+    #[test]
+    fn commands_skip_fetch_for_others() {
+        let cat = Personality::of::<Cat>();
+        assert_eq!(PetCommand::Follow.next(&cat), PetCommand::Stay);
+        assert_eq!(PetCommand::Stay.next(&cat), PetCommand::Follow);
+    }
+
+    #[test]
+    fn personalities_follow_the_species() {
+        let (dog, cat) = (Personality::of::<Dog>(), Personality::of::<Cat>());
+        assert!(dog.fetches && !cat.fetches);
+        assert!(dog.obedience > cat.obedience);
+        assert!(dog.follow_distance < cat.follow_distance);
+    }
+}
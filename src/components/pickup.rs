@@ -0,0 +1,264 @@
+//! Items lying in the world, collected by the player on contact.
+//!
+//! Pickups come from the LDtk `Pickup` entity (`item` enum and `quantity` int
+//! fields) or are spawned at runtime with [`SpawnPickup`]. They bob in place,
+//! fly toward a player within [`ATTRACTION_RADIUS`] and are collected by a
+//! sensor into the player [`Inventory`].
+
+use bevy::{ prelude::*, sprite::Anchor };
+use bevy_ecs_ldtk::{ ldtk::ldtk_fields::LdtkFields, EntityInstance };
+use bevy_rapier2d::{
+    dynamics::{ GravityScale, LockedAxes, RigidBody, Velocity },
+    geometry::{ Collider, CollisionGroups, Group, Sensor },
+    plugin::RapierContext,
+};
+
+use super::{
+    collision::{ ITEM_GROUP, NPC_GROUP, PLAYER_GROUP, PROJECTILE_GROUP },
+    items::{ Inventory, ItemId, Items },
+};
+use crate::{ entities::Player, plugins::gamestate::GameState };
+
+/// Distance from which pickups fly toward the player
+pub(crate) const ATTRACTION_RADIUS: f32 = 48.0;
+const ATTRACTION_SPEED: f32 = 180.0;
+const COLLECTION_RADIUS: f32 = 6.0;
+
+/// An item lying in the world
+#[derive(Component, Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Pickup {
+    pub item: ItemId,
+    pub quantity: u32,
+}
+
+impl From<&EntityInstance> for Pickup {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        Self {
+            item: ItemId(
+                entity_instance
+                    .get_enum_field("item")
+                    .expect("item field should be correctly typed")
+                    .clone()
+            ),
+            quantity: entity_instance
+                .get_int_field("quantity")
+                .map_or(1, |quantity| (*quantity).max(1) as u32),
+        }
+    }
+}
+
+/// Pickups placed in the level float instead of falling
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub(crate) struct Floating;
+
+/// Vertical oscillation of the sprite, in fraction of its height
+#[derive(Component, Clone, Copy, Debug)]
+pub(crate) struct Bob {
+    pub amplitude: f32,
+    pub frequency: f32,
+}
+
+impl Default for Bob {
+    fn default() -> Self {
+        Self {
+            amplitude: 0.15,
+            frequency: 3.0,
+        }
+    }
+}
+
+/// Sensor child of a pickup, colliding with the player only
+#[derive(Component, Clone, Copy, Debug)]
+pub(crate) struct PickupSensor;
+
+/// Request to spawn a [`Pickup`] at `position`, thrown with `velocity`
+#[derive(Event, Clone, Debug)]
+pub(crate) struct SpawnPickup {
+    pub item: ItemId,
+    pub quantity: u32,
+    pub position: Vec2,
+    pub velocity: Vec2,
+    /// Entity dropping the item, which can't collect it back right away
+    pub dropped_by: Option<Entity>,
+}
+
+/// The pickup is ignored by this entity until it gets out of
+/// [`ATTRACTION_RADIUS`]
+#[derive(Component, Clone, Copy, Debug)]
+pub(crate) struct DroppedBy(pub Entity);
+
+/// Sent when a pickup ends up in the inventory of `collector`
+#[derive(Event, Clone, Debug)]
+pub(crate) struct ItemPickedUp {
+    pub collector: Entity,
+    pub item: ItemId,
+    pub quantity: u32,
+}
+
+pub(crate) fn spawn_pickups(mut commands: Commands, mut events: EventReader<SpawnPickup>) {
+    for SpawnPickup { item, quantity, position, velocity, dropped_by } in events.read() {
+        let mut pickup = commands.spawn((
+            Name::new(format!("pickup {} x{}", item, quantity)),
+            Pickup {
+                item: item.clone(),
+                quantity: *quantity,
+            },
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::GOLD,
+                    custom_size: Some(Vec2::splat(8.0)),
+                    ..default()
+                },
+                transform: Transform::from_translation(position.extend(5.0)),
+                ..default()
+            },
+            Velocity::linear(*velocity),
+        ));
+        if let Some(dropped_by) = dropped_by {
+            pickup.insert(DroppedBy(*dropped_by));
+        }
+    }
+}
+
+/// Adds the physics body, the collection sensor and the bobbing to new pickups
+pub(crate) fn setup_pickups(
+    mut commands: Commands,
+    query: Query<(Entity, Has<Floating>), Added<Pickup>>
+) {
+    for (entity, floating) in &query {
+        commands
+            .entity(entity)
+            .insert((
+                RigidBody::Dynamic,
+                Collider::ball(4.0),
+                // Pickups only collide with the level
+                CollisionGroups::new(
+                    ITEM_GROUP,
+                    Group::ALL - PLAYER_GROUP - NPC_GROUP - PROJECTILE_GROUP - ITEM_GROUP
+                ),
+                GravityScale(if floating { 0.0 } else { 1.0 }),
+                LockedAxes::ROTATION_LOCKED,
+                Bob::default(),
+            ))
+            .with_children(|builder| {
+                builder.spawn((
+                    PickupSensor,
+                    Sensor,
+                    Collider::ball(COLLECTION_RADIUS),
+                    CollisionGroups::new(ITEM_GROUP, PLAYER_GROUP),
+                    TransformBundle::default(),
+                ));
+            });
+    }
+}
+
+pub(crate) fn bob_pickups(time: Res<Time>, mut query: Query<(&Bob, &mut Sprite)>) {
+    for (bob, mut sprite) in &mut query {
+        let offset = (time.elapsed_seconds() * bob.frequency).sin() * bob.amplitude;
+        sprite.anchor = Anchor::Custom(Vec2::new(0.0, -offset));
+    }
+}
+
+/// Pulls the pickups toward the closest player in range
+pub(crate) fn attract_pickups(
+    mut pickups: Query<
+        (&GlobalTransform, &mut Velocity, &mut GravityScale, Has<Floating>, Option<&DroppedBy>),
+        With<Pickup>
+    >,
+    players: Query<(Entity, &GlobalTransform), With<Player>>
+) {
+    for (transform, mut velocity, mut gravity_scale, floating, dropped_by) in &mut pickups {
+        let position = transform.translation().truncate();
+        let closest = players
+            .iter()
+            .filter(|(player, _)| dropped_by.map_or(true, |dropped_by| dropped_by.0 != *player))
+            .map(|(_, player)| player.translation().truncate() - position)
+            .filter(|offset| offset.length() < ATTRACTION_RADIUS)
+            .min_by(|a, b| a.length().total_cmp(&b.length()));
+        match closest {
+            Some(offset) => {
+                velocity.linvel = offset.normalize_or_zero() * ATTRACTION_SPEED;
+                gravity_scale.0 = 0.0;
+            }
+            None if floating => {
+                velocity.linvel = Vec2::ZERO;
+            }
+            None => {
+                gravity_scale.0 = 1.0;
+            }
+        }
+    }
+}
+
+/// Moves the pickups touched by a player into its inventory, pickups that
+/// don't fit stay in the world until there is room for them
+pub(crate) fn collect_pickups(
+    mut commands: Commands,
+    mut picked_up_events: EventWriter<ItemPickedUp>,
+    sensors: Query<(Entity, &Parent), With<PickupSensor>>,
+    pickups: Query<(&Pickup, Option<&DroppedBy>)>,
+    mut players: Query<&mut Inventory, With<Player>>,
+    rapier_context: Res<RapierContext>,
+    items: Items
+) {
+    let Some(registry) = items.registry() else {
+        return;
+    };
+    for (sensor, parent) in &sensors {
+        let Ok((pickup, dropped_by)) = pickups.get(**parent) else {
+            continue;
+        };
+        for (collider_a, collider_b, intersecting) in rapier_context.intersection_pairs_with(sensor) {
+            let player = if collider_a == sensor { collider_b } else { collider_a };
+            if dropped_by.is_some_and(|dropped_by| dropped_by.0 == player) {
+                continue;
+            }
+            let (true, Ok(mut inventory)) = (intersecting, players.get_mut(player)) else {
+                continue;
+            };
+            if inventory.add(registry, &pickup.item, pickup.quantity).is_ok() {
+                commands.entity(**parent).despawn_recursive();
+                picked_up_events.send(ItemPickedUp {
+                    collector: player,
+                    item: pickup.item.clone(),
+                    quantity: pickup.quantity,
+                });
+                break;
+            }
+        }
+    }
+}
+
+/// Lets the dropping entity collect the pickup again once it walked away
+pub(crate) fn forget_dropped_by(
+    mut commands: Commands,
+    pickups: Query<(Entity, &GlobalTransform, &DroppedBy)>,
+    transforms: Query<&GlobalTransform>
+) {
+    for (entity, transform, dropped_by) in &pickups {
+        let away = transforms.get(dropped_by.0).map_or(true, |dropper| {
+            dropper.translation().distance(transform.translation()) > ATTRACTION_RADIUS
+        });
+        if away {
+            commands.entity(entity).remove::<DroppedBy>();
+        }
+    }
+}
+
+pub(crate) fn plugin(app: &mut App) {
+    app.add_event::<SpawnPickup>()
+        .add_event::<ItemPickedUp>()
+        .add_systems(
+            Update,
+            (
+                spawn_pickups,
+                setup_pickups,
+                forget_dropped_by,
+                bob_pickups,
+                attract_pickups,
+                collect_pickups,
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing))
+        );
+}
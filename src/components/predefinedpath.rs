@@ -1,6 +1,6 @@
 use bevy::{
     asset::{ AssetServer, Assets, Handle },
-    ecs::{ component::Component, system::Query },
+    ecs::{ component::Component, query::Without, system::Query },
     math::{ IVec2, Vec2 },
     render::texture::Image,
     sprite::TextureAtlasLayout,
@@ -73,7 +73,26 @@ impl LdtkEntity for PredefinedPath {
     }
 }
 
-pub fn move_on_path(mut query: Query<(&mut Transform, &mut Velocity, &mut PredefinedPath)>) {
+/// Stops [`move_on_path`] for an entity, while it's busy with something else
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Component)]
+pub struct PatrolSuspended;
+
+impl PredefinedPath {
+    /// Index of the point closest to `position`
+    pub fn closest_point(&self, position: Vec2) -> Option<usize> {
+        self.points
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| {
+                a.distance_squared(position).total_cmp(&b.distance_squared(position))
+            })
+            .map(|(index, _)| index)
+    }
+}
+
+pub fn move_on_path(
+    mut query: Query<(&mut Transform, &mut Velocity, &mut PredefinedPath), Without<PatrolSuspended>>
+) {
     for (mut transform, mut velocity, mut path) in &mut query {
         if path.points.len() <= 1 {
             continue;
@@ -0,0 +1,383 @@
+//! Projectiles fired by abilities.
+//!
+//! Physical projectiles (Shoot, Fireball, FrozenOrb) are pooled sensor bodies
+//! resolving their hits from Rapier collision events. Instant abilities
+//! (LightningBolt) are hitscan rays, cast like in
+//! [`line_of_sight`](super::line_of_sight::line_of_sight).
+
+use bevy::prelude::*;
+use bevy_rapier2d::{
+    dynamics::{ GravityScale, LockedAxes, RigidBody, RigidBodyDisabled, Velocity },
+    geometry::{ ActiveEvents, Collider, ColliderDisabled, CollisionGroups, Group, Sensor },
+    pipeline::{ CollisionEvent, QueryFilter },
+    plugin::RapierContext,
+};
+use input_manager::action_state::ActionState;
+
+use super::{
+    collision::{ NPC_GROUP, PLAYER_GROUP, PROJECTILE_GROUP },
+    equipment::Stats,
+    facing::Facing,
+    health::{ DamageEvent, Health },
+    status_effects::ApplyStatusEffect,
+};
+use crate::{
+    entities::player::Ability,
+    plugins::{
+        dialogueview::not_in_dialogue,
+        gamestate::GameState,
+        rapier_utils::reciprocal_collisions,
+    },
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ProjectileKind {
+    /// A body travelling at `speed` px/s, spent after `lifetime` seconds
+    Physical {
+        speed: f32,
+        gravity_scale: f32,
+        radius: f32,
+        lifetime: f32,
+    },
+    /// An instant ray hitting everything up to `range` px
+    Hitscan {
+        range: f32,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ProjectileSpec {
+    pub kind: ProjectileKind,
+    pub damage: u32,
+    /// Number of targets the projectile goes through before being spent
+    pub pierce: u32,
+    pub collision_groups: CollisionGroups,
+    /// Status effect applied to the targets that are hit
+    pub status_effect: Option<&'static str>,
+    pub color: Color,
+}
+
+impl ProjectileSpec {
+    /// The projectile fired by an ability, if it fires one
+    pub fn for_ability(ability: Ability) -> Option<Self> {
+        // Player projectiles don't hit the player, NPCs or other projectiles
+        let collision_groups = CollisionGroups::new(
+            PROJECTILE_GROUP,
+            Group::ALL - PLAYER_GROUP - NPC_GROUP - PROJECTILE_GROUP
+        );
+        let spec = match ability {
+            Ability::Shoot =>
+                Self {
+                    kind: ProjectileKind::Physical {
+                        speed: 600.0,
+                        gravity_scale: 0.0,
+                        radius: 2.0,
+                        lifetime: 1.0,
+                    },
+                    damage: 10,
+                    pierce: 0,
+                    collision_groups,
+                    status_effect: None,
+                    color: Color::WHITE,
+                },
+            Ability::Fireball =>
+                Self {
+                    kind: ProjectileKind::Physical {
+                        speed: 350.0,
+                        gravity_scale: 0.2,
+                        radius: 5.0,
+                        lifetime: 2.0,
+                    },
+                    damage: 20,
+                    pierce: 0,
+                    collision_groups,
+                    status_effect: Some("burn"),
+                    color: Color::ORANGE_RED,
+                },
+            Ability::FrozenOrb =>
+                Self {
+                    kind: ProjectileKind::Physical {
+                        speed: 150.0,
+                        gravity_scale: 0.0,
+                        radius: 6.0,
+                        lifetime: 3.0,
+                    },
+                    damage: 5,
+                    pierce: u32::MAX,
+                    collision_groups,
+                    status_effect: Some("freeze"),
+                    color: Color::CYAN,
+                },
+            Ability::LightningBolt =>
+                Self {
+                    kind: ProjectileKind::Hitscan { range: 400.0 },
+                    damage: 25,
+                    pierce: 2,
+                    collision_groups,
+                    status_effect: None,
+                    color: Color::YELLOW,
+                },
+            _ => {
+                return None;
+            }
+        };
+        Some(spec)
+    }
+}
+
+#[derive(Component, Clone, Debug)]
+pub(crate) struct Projectile {
+    pub owner: Entity,
+    pub damage: u32,
+    pub pierce_left: u32,
+    pub status_effect: Option<&'static str>,
+    pub lifetime: Timer,
+    /// Targets already hit, so piercing projectiles hit each target only once
+    pub hits: Vec<Entity>,
+    spent: bool,
+}
+
+/// Marker of the projectiles waiting in the [`ProjectilePool`]
+#[derive(Component)]
+pub(crate) struct Pooled;
+
+/// Disabled projectile entities, reused by the next spawned projectiles
+#[derive(Resource, Default)]
+pub(crate) struct ProjectilePool {
+    free: Vec<Entity>,
+}
+
+impl ProjectilePool {
+    fn release(&mut self, commands: &mut Commands, entity: Entity, projectile: &mut Projectile) {
+        projectile.spent = true;
+        commands
+            .entity(entity)
+            .insert((Pooled, ColliderDisabled, RigidBodyDisabled, Visibility::Hidden));
+        self.free.push(entity);
+    }
+}
+
+/// Request to fire a projectile from `origin` toward `direction`
+#[derive(Event, Clone, Debug)]
+pub(crate) struct SpawnProjectile {
+    pub owner: Entity,
+    pub origin: Vec2,
+    pub direction: Vec2,
+    pub spec: ProjectileSpec,
+}
+
+/// Damages the target and applies the status effect of the projectile
+fn hit(
+    target: Entity,
+    owner: Entity,
+    damage: u32,
+    status_effect: Option<&'static str>,
+    damage_events: &mut EventWriter<DamageEvent>,
+    status_events: &mut EventWriter<ApplyStatusEffect>
+) {
+    damage_events.send(DamageEvent {
+        target,
+        amount: damage,
+        source: Some(owner),
+    });
+    if let Some(effect) = status_effect {
+        status_events.send(ApplyStatusEffect {
+            target,
+            effect: effect.to_owned(),
+        });
+    }
+}
+
+pub(crate) fn fire_projectile_abilities(
+    query: Query<
+        (Entity, &ActionState<Ability>, &GlobalTransform, Option<&Facing>, Option<&Stats>)
+    >,
+    mut spawn_events: EventWriter<SpawnProjectile>
+) {
+    for (owner, ability_state, transform, facing, stats) in &query {
+        for ability in ability_state.get_just_pressed() {
+            let Some(mut spec) = ProjectileSpec::for_ability(ability) else {
+                continue;
+            };
+            if let Some(stats) = stats {
+                spec.damage = stats.scale_damage(spec.damage);
+            }
+            spawn_events.send(SpawnProjectile {
+                owner,
+                origin: transform.translation().truncate(),
+                direction: facing.map_or(Vec2::X, |facing| facing.0),
+                spec,
+            });
+        }
+    }
+}
+
+pub(crate) fn spawn_projectiles(
+    mut commands: Commands,
+    mut spawn_events: EventReader<SpawnProjectile>,
+    mut pool: ResMut<ProjectilePool>,
+    rapier_context: Res<RapierContext>,
+    targets: Query<(), With<Health>>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut status_events: EventWriter<ApplyStatusEffect>
+) {
+    for SpawnProjectile { owner, origin, direction, spec } in spawn_events.read() {
+        let direction = direction.normalize_or_zero();
+        match spec.kind {
+            ProjectileKind::Hitscan { range } => {
+                let mut intersections = Vec::new();
+                rapier_context.intersections_with_ray(
+                    *origin,
+                    direction,
+                    range,
+                    true,
+                    QueryFilter::new()
+                        .exclude_sensors()
+                        .exclude_collider(*owner)
+                        .groups(spec.collision_groups),
+                    |entity, intersection| {
+                        intersections.push((entity, intersection.toi));
+                        true
+                    }
+                );
+                intersections.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+
+                let mut pierce_left = spec.pierce;
+                for (entity, toi) in intersections {
+                    // Anything without health (walls...) stops the ray
+                    if !targets.contains(entity) {
+                        debug!("Hitscan from {:?} stopped by {:?} at {}", owner, entity, toi);
+                        break;
+                    }
+                    hit(
+                        entity,
+                        *owner,
+                        spec.damage,
+                        spec.status_effect,
+                        &mut damage_events,
+                        &mut status_events
+                    );
+                    if pierce_left == 0 {
+                        break;
+                    }
+                    pierce_left -= 1;
+                }
+            }
+            ProjectileKind::Physical { speed, gravity_scale, radius, lifetime } => {
+                let entity = pool.free
+                    .pop()
+                    .unwrap_or_else(|| commands.spawn(Name::new("projectile")).id());
+                commands
+                    .entity(entity)
+                    .remove::<(Pooled, ColliderDisabled, RigidBodyDisabled)>()
+                    .insert((
+                        Projectile {
+                            owner: *owner,
+                            damage: spec.damage,
+                            pierce_left: spec.pierce,
+                            status_effect: spec.status_effect,
+                            lifetime: Timer::from_seconds(lifetime, TimerMode::Once),
+                            hits: Vec::new(),
+                            spent: false,
+                        },
+                        SpriteBundle {
+                            sprite: Sprite {
+                                color: spec.color,
+                                custom_size: Some(Vec2::splat(radius * 2.0)),
+                                ..default()
+                            },
+                            transform: Transform::from_translation(origin.extend(10.0)),
+                            ..default()
+                        },
+                        RigidBody::Dynamic,
+                        Collider::ball(radius),
+                        Sensor,
+                        ActiveEvents::COLLISION_EVENTS,
+                        spec.collision_groups,
+                        GravityScale(gravity_scale),
+                        Velocity::linear(direction * speed),
+                        LockedAxes::ROTATION_LOCKED,
+                    ));
+            }
+        }
+    }
+}
+
+/// Turns the collision events of projectiles into damage
+pub(crate) fn resolve_projectile_hits(
+    mut commands: Commands,
+    mut collisions: EventReader<CollisionEvent>,
+    mut projectiles: Query<&mut Projectile, Without<Pooled>>,
+    targets: Query<(), With<Health>>,
+    obstacles: Query<(), (With<Collider>, Without<Sensor>)>,
+    mut pool: ResMut<ProjectilePool>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut status_events: EventWriter<ApplyStatusEffect>
+) {
+    reciprocal_collisions(&mut collisions, |projectile_entity, other, _, start| {
+        let Ok(mut projectile) = projectiles.get_mut(*projectile_entity) else {
+            return false;
+        };
+        if
+            !start ||
+            projectile.spent ||
+            *other == projectile.owner ||
+            projectile.hits.contains(other)
+        {
+            return true;
+        }
+        if targets.contains(*other) {
+            projectile.hits.push(*other);
+            hit(
+                *other,
+                projectile.owner,
+                projectile.damage,
+                projectile.status_effect,
+                &mut damage_events,
+                &mut status_events
+            );
+            if projectile.pierce_left == 0 {
+                pool.release(&mut commands, *projectile_entity, &mut projectile);
+            } else {
+                projectile.pierce_left -= 1;
+            }
+        } else if obstacles.contains(*other) {
+            pool.release(&mut commands, *projectile_entity, &mut projectile);
+        }
+        true
+    });
+}
+
+pub(crate) fn expire_projectiles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut projectiles: Query<(Entity, &mut Projectile), Without<Pooled>>,
+    mut pool: ResMut<ProjectilePool>
+) {
+    for (entity, mut projectile) in &mut projectiles {
+        if projectile.spent {
+            continue;
+        }
+        if projectile.lifetime.tick(time.delta()).just_finished() {
+            pool.release(&mut commands, entity, &mut projectile);
+        }
+    }
+}
+
+pub(crate) fn plugin(app: &mut App) {
+    app.init_resource::<ProjectilePool>()
+        .add_event::<SpawnProjectile>()
+        .add_systems(
+            Update,
+            (
+                fire_projectile_abilities.run_if(not_in_dialogue),
+                spawn_projectiles,
+                resolve_projectile_hits,
+                expire_projectiles,
+            )
+                .chain()
+                .before(super::health::apply_damage)
+                .before(super::status_effects::apply_status_effects)
+                .run_if(in_state(GameState::Playing))
+        );
+}
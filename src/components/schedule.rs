@@ -0,0 +1,446 @@
+//! Daily schedules of NPCs.
+//!
+//! A schedule lists what an NPC does from a given [`ClockTime`] on: where it
+//! goes, through pathfinding, and which dialogue node it offers. It's authored
+//! in the LDtk `schedule` field of the NPC, one `HH:MM Goal [DialogueNode]`
+//! entry per line, or as a named schedule of `data/npcs.schedules.ron`
+//! referenced by the `scheduleName` field.
+
+use std::{ collections::HashMap, str::FromStr };
+
+use bevy::prelude::*;
+use bevy_asset_loader::{
+    asset_collection::AssetCollection,
+    loading_state::{ config::{ ConfigureLoadingState, LoadingStateConfig }, LoadingStateAppExt },
+};
+use bevy_ecs_ldtk::{ ldtk::ldtk_fields::LdtkFields, EntityInstance };
+use bevy_rapier2d::dynamics::Velocity;
+use bevy_yarnspinner::prelude::DialogueRunner;
+use input_manager::action_state::ActionState;
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::{
+    ai::{ fatigue::Asleep, movement::{ Busy, MoveTo } },
+    interactions::InteractionSensor,
+    predefinedpath::PatrolSuspended,
+    shapeshift::Shapeshifted,
+};
+use crate::{
+    entities::{ npc::Npc, Player },
+    plugins::{
+        clock::{ ClockTime, ClockTimeError, GameClock },
+        dialogueview::not_in_dialogue,
+        gamestate::GameState,
+        input::Action,
+        ron_asset::RonAssetAppExt,
+    },
+};
+
+/// Walking speed of NPCs going about their day
+const NPC_SPEED: f32 = 40.0;
+/// Distance at which an NPC stops from the entity it goes to
+const GOAL_TOLERANCE: f32 = 16.0;
+
+/// Where an NPC goes
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub enum Goal {
+    /// Stays where it is
+    Stay,
+    /// Back to where it spawned
+    Home,
+    /// Resumes its `PredefinedPath`
+    Patrol,
+    /// The closest LDtk entity with this identifier, like `Cauldron`
+    Entity(String),
+    Position(f32, f32),
+}
+
+impl FromStr for Goal {
+    type Err = ScheduleError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid_position = |_| ScheduleError::Position(value.to_string());
+        match value {
+            "" => Err(ScheduleError::MissingGoal),
+            "Stay" => Ok(Self::Stay),
+            "Home" => Ok(Self::Home),
+            "Patrol" => Ok(Self::Patrol),
+            _ =>
+                match value.split_once(',') {
+                    Some((x, y)) =>
+                        Ok(
+                            Self::Position(
+                                x.trim().parse().map_err(invalid_position)?,
+                                y.trim().parse().map_err(invalid_position)?
+                            )
+                        ),
+                    None => Ok(Self::Entity(value.to_string())),
+                }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum ScheduleError {
+    #[error(transparent)]
+    Time(#[from] ClockTimeError),
+    #[error("missing goal")]
+    MissingGoal,
+    #[error("invalid position {0:?}, expected x,y")]
+    Position(String),
+}
+
+/// What an NPC does from `at` on, until the next entry
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ScheduleEntry {
+    pub at: ClockTime,
+    pub goal: Goal,
+    /// Dialogue node offered meanwhile
+    #[serde(default)]
+    pub dialogue: Option<String>,
+}
+
+impl FromStr for ScheduleEntry {
+    type Err = ScheduleError;
+
+    /// Parses `HH:MM Goal [DialogueNode]`
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut words = value.split_whitespace();
+        let at = words.next().unwrap_or_default().parse()?;
+        let goal = words.next().unwrap_or_default().parse()?;
+        let dialogue = words.next().map(str::to_string);
+        Ok(Self { at, goal, dialogue })
+    }
+}
+
+/// Named schedules, shared by the NPCs referencing them
+#[derive(Asset, TypePath, Deserialize, Debug, Default)]
+#[serde(transparent)]
+pub struct ScheduleRegistry {
+    schedules: HashMap<String, Vec<ScheduleEntry>>,
+}
+
+impl ScheduleRegistry {
+    pub fn get(&self, name: &str) -> Option<&Vec<ScheduleEntry>> {
+        self.schedules.get(name)
+    }
+}
+
+#[derive(AssetCollection, Resource)]
+pub(crate) struct ScheduleAssets {
+    #[asset(path = "data/npcs.schedules.ron")]
+    pub registry: Handle<ScheduleRegistry>,
+}
+
+#[derive(Component, Clone, Debug, Default)]
+pub struct NpcSchedule {
+    /// Sorted by time
+    entries: Vec<ScheduleEntry>,
+    /// Schedule of the [`ScheduleRegistry`] to use, until it's loaded
+    name: Option<String>,
+    /// Where the NPC spawned
+    home: Option<Vec2>,
+    /// Index of the entry being followed
+    current: Option<usize>,
+    /// Where the current entry sends the NPC
+    target: Option<Vec2>,
+}
+
+impl NpcSchedule {
+    pub fn new(mut entries: Vec<ScheduleEntry>) -> Self {
+        entries.sort_by_key(|entry| entry.at);
+        Self { entries, ..default() }
+    }
+
+    pub fn named(name: String) -> Self {
+        Self { name: Some(name), ..default() }
+    }
+
+    /// Index of the entry to follow at `time`: the last one started, or the
+    /// last one of the previous day
+    fn active(&self, time: ClockTime) -> Option<usize> {
+        let started = self.entries.partition_point(|entry| entry.at <= time);
+        match started {
+            0 => self.entries.len().checked_sub(1),
+            _ => Some(started - 1),
+        }
+    }
+}
+
+/// Dialogue node currently offered by an NPC
+#[derive(Component, Clone, Debug, PartialEq, Eq, Reflect)]
+pub struct ActiveDialogue {
+    pub node: String,
+}
+
+pub(crate) fn attach_schedules(
+    mut commands: Commands,
+    npcs: Query<(Entity, &EntityInstance), Added<Npc>>
+) {
+    for (entity, entity_instance) in &npcs {
+        let schedule = if let Ok(lines) = entity_instance.get_string_field("schedule") {
+            let entries = lines
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .filter_map(|line| {
+                    line.parse::<ScheduleEntry>()
+                        .map_err(|error| {
+                            warn!("Invalid schedule entry {:?} for {}: {}", line, entity_instance.iid, error)
+                        })
+                        .ok()
+                })
+                .collect();
+            NpcSchedule::new(entries)
+        } else if let Ok(name) = entity_instance.get_string_field("scheduleName") {
+            NpcSchedule::named(name.clone())
+        } else {
+            continue;
+        };
+        commands.entity(entity).insert(schedule);
+    }
+}
+
+/// Fills the schedules referencing the [`ScheduleRegistry`]
+pub(crate) fn resolve_named_schedules(
+    mut schedules: Query<&mut NpcSchedule>,
+    assets: Res<ScheduleAssets>,
+    registries: Res<Assets<ScheduleRegistry>>
+) {
+    let Some(registry) = registries.get(&assets.registry) else {
+        return;
+    };
+    for mut schedule in &mut schedules {
+        let Some(name) = schedule.name.take() else {
+            continue;
+        };
+        match registry.get(&name) {
+            Some(entries) => {
+                *schedule = NpcSchedule { home: schedule.home, ..NpcSchedule::new(entries.clone()) };
+            }
+            None => warn!("Unknown schedule {:?}", name),
+        }
+    }
+}
+
+/// Sends the NPCs where their schedule says, once per entry, and back there
+/// when something else moved them away meanwhile. An entry starting while the
+/// NPC is [`Busy`] waits for the action to end.
+pub(crate) fn follow_schedules(
+    mut commands: Commands,
+    clock: Res<GameClock>,
+    mut npcs: Query<
+        (Entity, &GlobalTransform, &mut NpcSchedule, Option<&MoveTo>, Has<Busy>),
+        (Without<Asleep>, Without<Shapeshifted>)
+    >,
+    landmarks: Query<(&EntityInstance, &GlobalTransform)>
+) {
+    let now = clock.time();
+    for (entity, transform, mut schedule, move_to, busy) in &mut npcs {
+        let position = transform.translation().truncate();
+        let home = *schedule.home.get_or_insert(position);
+        let Some(index) = schedule.active(now) else {
+            continue;
+        };
+        if schedule.current == Some(index) {
+            let away = schedule.target.filter(|target| (target.x - position.x).abs() > GOAL_TOLERANCE);
+            if let Some(target) = away.filter(|_| move_to.is_none() && !busy) {
+                debug!("{:?} heads back to {}", entity, target);
+                commands
+                    .entity(entity)
+                    .insert((PatrolSuspended, MoveTo::new(target, NPC_SPEED).with_tolerance(GOAL_TOLERANCE)));
+            }
+            continue;
+        }
+        // Not to undo what the action set up, retried once it ends
+        if busy {
+            continue;
+        }
+        schedule.current = Some(index);
+        let entry = schedule.entries[index].clone();
+        debug!("{:?} at {}: {:?}", entity, now, entry);
+
+        let mut entity_commands = commands.entity(entity);
+        match &entry.dialogue {
+            Some(node) => entity_commands.insert(ActiveDialogue { node: node.clone() }),
+            None => entity_commands.remove::<ActiveDialogue>(),
+        };
+        let target = match &entry.goal {
+            Goal::Stay => None,
+            Goal::Home => Some(home),
+            Goal::Position(x, y) => Some(Vec2::new(*x, *y)),
+            Goal::Entity(identifier) => {
+                let closest = landmarks
+                    .iter()
+                    .filter(|(instance, _)| &instance.identifier == identifier)
+                    .map(|(_, landmark)| landmark.translation().truncate())
+                    .min_by(|a, b| {
+                        a.distance_squared(position).total_cmp(&b.distance_squared(position))
+                    });
+                if closest.is_none() {
+                    warn!("No {} for {:?} to go to", identifier, entity);
+                }
+                closest
+            }
+            Goal::Patrol => {
+                schedule.target = None;
+                entity_commands.remove::<(PatrolSuspended, MoveTo)>();
+                continue;
+            }
+        };
+        schedule.target = target;
+        entity_commands.insert((PatrolSuspended, Velocity::zero()));
+        match target {
+            Some(target) => {
+                entity_commands.insert(MoveTo::new(target, NPC_SPEED).with_tolerance(GOAL_TOLERANCE));
+            }
+            None => {
+                entity_commands.remove::<MoveTo>();
+            }
+        }
+    }
+}
+
+/// Starts the [`ActiveDialogue`] of the NPC closest to a player pressing
+/// [`Action::Interact`]
+pub(crate) fn start_scheduled_dialogues(
+    sensors: Query<(&InteractionSensor, &Parent)>,
+    players: Query<&ActionState<Action>, With<Player>>,
+    dialogues: Query<&ActiveDialogue>,
+    mut dialogue_runners: Query<&mut DialogueRunner>
+) {
+    for (sensor, parent) in &sensors {
+        let Ok(action_state) = players.get(**parent) else {
+            continue;
+        };
+        if !action_state.just_pressed(&Action::Interact) {
+            continue;
+        }
+        let Some(dialogue) = sensor.closest_entity.and_then(|entity| dialogues.get(entity).ok()) else {
+            continue;
+        };
+        let Ok(mut dialogue_runner) = dialogue_runners.get_single_mut() else {
+            continue;
+        };
+        if dialogue_runner.is_running() {
+            continue;
+        }
+        if let Err(error) = dialogue_runner.try_start_node(&dialogue.node) {
+            warn!("Could not start the dialogue {:?}: {}", dialogue.node, error);
+        }
+    }
+}
+
+pub(crate) fn plugin(app: &mut App) {
+    app.register_ron_asset::<ScheduleRegistry>(&["schedules.ron"])
+        .configure_loading_state(
+            LoadingStateConfig::new(GameState::SplashScreen).load_collection::<ScheduleAssets>()
+        )
+        .register_type::<ActiveDialogue>()
+        .add_systems(
+            Update,
+            (
+                attach_schedules,
+                resolve_named_schedules,
+                follow_schedules,
+                start_scheduled_dialogues.run_if(not_in_dialogue),
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing))
+        );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(value: &str) -> ScheduleEntry {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_entries() {
+        assert_eq!(entry("08:00 Cauldron Brewing"), ScheduleEntry {
+            at: ClockTime::new(8, 0),
+            goal: Goal::Entity("Cauldron".into()),
+            dialogue: Some("Brewing".into()),
+        });
+        assert_eq!(entry("12:00 Patrol"), ScheduleEntry {
+            at: ClockTime::new(12, 0),
+            goal: Goal::Patrol,
+            dialogue: None,
+        });
+        assert_eq!(entry("20:30 Home").goal, Goal::Home);
+        assert_eq!(entry("21:00 Stay").goal, Goal::Stay);
+        assert_eq!(entry("06:15 120,-48.5").goal, Goal::Position(120.0, -48.5));
+    }
+
+    #[test]
+    fn rejects_invalid_entries() {
+        assert_eq!("".parse::<ScheduleEntry>(), Err(ClockTimeError("".into()).into()));
+        assert_eq!("25:00 Home".parse::<ScheduleEntry>(), Err(ClockTimeError("25:00".into()).into()));
+        assert_eq!("08:00".parse::<ScheduleEntry>(), Err(ScheduleError::MissingGoal));
+        assert_eq!("08:00 1,y".parse::<ScheduleEntry>(), Err(ScheduleError::Position("1,y".into())));
+    }
+
+    #[test]
+    fn follows_the_last_started_entry() {
+        let schedule = NpcSchedule::new(vec![entry("20:00 Home"), entry("08:00 Patrol"), entry("12:00 Stay")]);
+        // Sorted by time
+        assert_eq!(schedule.active(ClockTime::new(8, 0)), Some(0));
+        assert_eq!(schedule.active(ClockTime::new(11, 59)), Some(0));
+        assert_eq!(schedule.active(ClockTime::new(12, 0)), Some(1));
+        assert_eq!(schedule.active(ClockTime::new(23, 0)), Some(2));
+        // Before the first entry of the day, the last one of the previous day
+        assert_eq!(schedule.active(ClockTime::new(3, 0)), Some(2));
+    }
+
+    #[test]
+    fn empty_schedules_have_no_active_entry() {
+        assert_eq!(NpcSchedule::default().active(ClockTime::new(12, 0)), None);
+    }
+
+    fn follow(entries: &[&str], busy: bool) -> (App, Entity) {
+        let mut app = App::new();
+        app.init_resource::<GameClock>().add_systems(Update, follow_schedules);
+        let schedule = NpcSchedule::new(entries.iter().map(|value| entry(value)).collect());
+        let npc = app.world.spawn((GlobalTransform::default(), schedule)).id();
+        if busy {
+            app.world.entity_mut(npc).insert((Busy, PatrolSuspended));
+        }
+        app.update();
+        (app, npc)
+    }
+
+    #[test]
+    fn entries_wait_for_the_action_to_end() {
+        // The clock starts at 08:00
+        let (mut app, npc) = follow(&["08:00 100,0"], true);
+        assert!(!app.world.entity(npc).contains::<MoveTo>());
+        assert!(!app.world.entity(npc).contains::<Velocity>());
+
+        // The action ends, and takes its patrol suspension with it
+        app.world.entity_mut(npc).remove::<(Busy, PatrolSuspended)>();
+        app.update();
+        let move_to = app.world.get::<MoveTo>(npc).unwrap();
+        assert_eq!(move_to.target, Vec2::new(100.0, 0.0));
+        assert!(app.world.entity(npc).contains::<PatrolSuspended>());
+    }
+
+    #[test]
+    fn patrols_resume_once_the_action_ends() {
+        let (mut app, npc) = follow(&["08:00 Patrol"], true);
+        assert!(app.world.entity(npc).contains::<PatrolSuspended>());
+
+        app.world.entity_mut(npc).remove::<Busy>();
+        app.update();
+        assert!(!app.world.entity(npc).contains::<PatrolSuspended>());
+    }
+
+    #[test]
+    fn idle_npcs_start_entries_right_away() {
+        let (app, npc) = follow(&["08:00 100,0"], false);
+        assert!(app.world.entity(npc).contains::<MoveTo>());
+        assert!(app.world.entity(npc).contains::<PatrolSuspended>());
+    }
+}
@@ -0,0 +1,219 @@
+//! Temporary transformations of an entity into another form (🐑 PolymorphSheep…)
+//!
+//! The sprite, collider, AI and movement components of the entity are stashed
+//! while it is transformed, and restored when it reverts after the duration of
+//! the form or when it takes damage.
+
+use bevy::{ ecs::system::EntityCommand, hierarchy::despawn_with_children_recursive, prelude::* };
+use bevy_rapier2d::{
+    dynamics::Velocity,
+    geometry::Collider,
+    pipeline::QueryFilter,
+    plugin::RapierContext,
+};
+use big_brain::thinker::{ HasThinker, ThinkerBuilder };
+use input_manager::action_state::ActionState;
+use seldom_state::prelude::StateMachine;
+
+use super::{ facing::Facing, health::DamageEvent, predefinedpath::PredefinedPath };
+use crate::{
+    entities::{ cat::Cat, dog::Dog, enemy::Enemy, kade::Kade, npc::Npc, player::Ability },
+    plugins::{ dialogueview::not_in_dialogue, gamestate::GameState },
+};
+
+/// Archetype an entity is transformed into
+#[derive(Debug)]
+pub(crate) struct ShapeshiftForm {
+    pub name: &'static str,
+    pub texture: &'static str,
+    pub half_extents: Vec2,
+    /// Wandering speed in px/s
+    pub move_speed: f32,
+    /// Duration in seconds
+    pub duration: f32,
+    pub reverts_on_damage: bool,
+}
+
+pub(crate) const SHEEP: ShapeshiftForm = ShapeshiftForm {
+    name: "sheep",
+    texture: "sheep.png",
+    half_extents: Vec2::new(10.0, 8.0),
+    move_speed: 15.0,
+    duration: 8.0,
+    reverts_on_damage: true,
+};
+
+type Restore = Box<dyn FnOnce(&mut EntityWorldMut) + Send + Sync>;
+
+/// An entity transformed into a [`ShapeshiftForm`]
+#[derive(Component)]
+pub(crate) struct Shapeshifted {
+    pub form: &'static ShapeshiftForm,
+    pub timer: Timer,
+    restores: Vec<Restore>,
+}
+
+/// Takes the `T` component out of the entity, to be restored on revert
+fn stash<T: Component>(entity: &mut EntityWorldMut, restores: &mut Vec<Restore>) {
+    if let Some(component) = entity.take::<T>() {
+        restores.push(
+            Box::new(move |entity| {
+                entity.insert(component);
+            })
+        );
+    }
+}
+
+/// Transforms the entity, or restarts the timer of an already transformed one
+pub(crate) struct Shapeshift(pub &'static ShapeshiftForm);
+
+impl EntityCommand for Shapeshift {
+    fn apply(self, id: Entity, world: &mut World) {
+        let form = self.0;
+        let texture: Handle<Image> = world.resource::<AssetServer>().load(form.texture);
+        let Some(mut entity) = world.get_entity_mut(id) else {
+            return;
+        };
+        if let Some(mut shapeshifted) = entity.get_mut::<Shapeshifted>() {
+            shapeshifted.timer.reset();
+            return;
+        }
+
+        let mut restores = Vec::new();
+        // Look
+        stash::<Handle<Image>>(&mut entity, &mut restores);
+        stash::<Sprite>(&mut entity, &mut restores);
+        stash::<TextureAtlas>(&mut entity, &mut restores);
+        stash::<Collider>(&mut entity, &mut restores);
+        // Movement and AI
+        stash::<PredefinedPath>(&mut entity, &mut restores);
+        stash::<StateMachine>(&mut entity, &mut restores);
+        stash::<ThinkerBuilder>(&mut entity, &mut restores);
+        let thinker = entity.take::<HasThinker>().map(|has_thinker| has_thinker.entity());
+
+        entity.insert((
+            texture,
+            Sprite::default(),
+            Collider::cuboid(form.half_extents.x, form.half_extents.y),
+            Velocity::zero(),
+            ShapeshiftWander {
+                speed: form.move_speed,
+                direction: 1.0,
+                timer: Timer::from_seconds(2.0, TimerMode::Repeating),
+            },
+            Shapeshifted {
+                form,
+                timer: Timer::from_seconds(form.duration, TimerMode::Once),
+                restores,
+            },
+        ));
+        debug!("{:?} turned into a {}", id, form.name);
+
+        // The thinker is spawned again from the restored ThinkerBuilder
+        if let Some(thinker) = thinker {
+            despawn_with_children_recursive(world, thinker);
+        }
+    }
+}
+
+/// Reverts a transformed entity to its original form
+pub(crate) struct Revert;
+
+impl EntityCommand for Revert {
+    fn apply(self, id: Entity, world: &mut World) {
+        let Some(mut entity) = world.get_entity_mut(id) else {
+            return;
+        };
+        let Some(shapeshifted) = entity.take::<Shapeshifted>() else {
+            return;
+        };
+        entity.remove::<(Handle<Image>, Sprite, Collider, ShapeshiftWander)>();
+        entity.insert(Velocity::zero());
+        for restore in shapeshifted.restores {
+            restore(&mut entity);
+        }
+        debug!("{:?} is no longer a {}", id, shapeshifted.form.name);
+    }
+}
+
+/// Minimal AI of the transformed entities: walk a bit, then turn around
+#[derive(Component, Clone, Debug)]
+pub(crate) struct ShapeshiftWander {
+    pub speed: f32,
+    direction: f32,
+    timer: Timer,
+}
+
+/// The form an ability transforms its target into, if it does
+fn form_for_ability(ability: Ability) -> Option<&'static ShapeshiftForm> {
+    match ability {
+        Ability::PolymorphSheep => Some(&SHEEP),
+        _ => None,
+    }
+}
+
+const SHAPESHIFT_RANGE: f32 = 200.0;
+
+/// Transforms the first actor in front of the caster
+pub(crate) fn cast_shapeshift_abilities(
+    mut commands: Commands,
+    casters: Query<(Entity, &ActionState<Ability>, &GlobalTransform, Option<&Facing>)>,
+    targets: Query<(), Or<(With<Npc>, With<Kade>, With<Dog>, With<Cat>, With<Enemy>)>>,
+    rapier_context: Res<RapierContext>
+) {
+    for (caster, ability_state, transform, facing) in &casters {
+        for ability in ability_state.get_just_pressed() {
+            let Some(form) = form_for_ability(ability) else {
+                continue;
+            };
+            let Some((target, _)) = rapier_context.cast_ray(
+                transform.translation().truncate(),
+                facing.map_or(Vec2::X, |facing| facing.0),
+                SHAPESHIFT_RANGE,
+                false,
+                QueryFilter::new().exclude_sensors().exclude_collider(caster)
+            ) else {
+                continue;
+            };
+            if targets.contains(target) {
+                commands.entity(target).add(Shapeshift(form));
+            }
+        }
+    }
+}
+
+pub(crate) fn wander(time: Res<Time>, mut query: Query<(&mut ShapeshiftWander, &mut Velocity)>) {
+    for (mut wander, mut velocity) in &mut query {
+        if wander.timer.tick(time.delta()).just_finished() {
+            wander.direction = -wander.direction;
+        }
+        velocity.linvel.x = wander.direction * wander.speed;
+    }
+}
+
+pub(crate) fn revert_shapeshifts(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Shapeshifted)>,
+    mut damage_events: EventReader<DamageEvent>
+) {
+    let damaged = damage_events
+        .read()
+        .map(|event| event.target)
+        .collect::<Vec<_>>();
+    for (entity, mut shapeshifted) in &mut query {
+        let expired = shapeshifted.timer.tick(time.delta()).finished();
+        if expired || (shapeshifted.form.reverts_on_damage && damaged.contains(&entity)) {
+            commands.entity(entity).add(Revert);
+        }
+    }
+}
+
+pub(crate) fn plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        (cast_shapeshift_abilities.run_if(not_in_dialogue), wander, revert_shapeshifts).run_if(
+            in_state(GameState::Playing)
+        )
+    );
+}
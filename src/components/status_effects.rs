@@ -0,0 +1,454 @@
+//! Buffs and debuffs (poison, burn, freeze, slow, regen…)
+//!
+//! Effects are defined in `data/status_effects.effects.ron`, a map from the
+//! effect id to its [`StatusEffectDefinition`]:
+//!
+//! ```ron
+//! {
+//!     "poison": (
+//!         duration: 6.0,
+//!         tick: Some((interval: 1.0, kind: Damage(2))),
+//!         stacking: Stack(max: 5),
+//!         tint: Some((0.6, 1.0, 0.4, 1.0)),
+//!     ),
+//!     "freeze": (duration: 2.0, stacking: Refresh, blocks_input: true, gravity_scale: 2.0),
+//! }
+//! ```
+
+use std::time::Duration;
+
+use bevy::{ prelude::*, utils::HashMap };
+use bevy_asset_loader::{
+    asset_collection::AssetCollection,
+    loading_state::{ config::{ ConfigureLoadingState, LoadingStateConfig }, LoadingStateAppExt },
+};
+use bevy_rapier2d::dynamics::GravityScale;
+use serde::Deserialize;
+
+use super::{ climbing::Climber, health::{ DamageEvent, HealEvent } };
+use crate::plugins::{ gamestate::GameState, ron_asset::RonAssetAppExt };
+
+/// What happens when an effect is applied to an entity already affected by it
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum StatusEffectStacking {
+    /// Restart the duration
+    #[default]
+    Refresh,
+    /// Add a stack (up to `max`) and restart the duration
+    Stack {
+        max: u32,
+    },
+    /// Keep the running effect untouched
+    Ignore,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum StatusTickKind {
+    Damage(u32),
+    Heal(u32),
+}
+
+/// Periodic damage or heal, multiplied by the number of stacks
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub(crate) struct StatusTick {
+    pub interval: f32,
+    pub kind: StatusTickKind,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct StatusEffectDefinition {
+    /// Duration in seconds
+    pub duration: f32,
+    #[serde(default)]
+    pub tick: Option<StatusTick>,
+    #[serde(default)]
+    pub stacking: StatusEffectStacking,
+    /// Multiplier applied to the movement speed, once per stack
+    #[serde(default = "one")]
+    pub move_speed: f32,
+    /// Multiplier applied to the dealt damage, once per stack
+    #[serde(default = "one")]
+    pub damage: f32,
+    /// Armor added, once per stack
+    #[serde(default)]
+    pub armor: i32,
+    /// Multiplier applied to the gravity scale, once per stack
+    #[serde(default = "one")]
+    pub gravity_scale: f32,
+    /// Whether the affected entity ignores movement inputs
+    #[serde(default)]
+    pub blocks_input: bool,
+    /// RGBA tint applied to the sprite of the affected entity
+    #[serde(default)]
+    pub tint: Option<(f32, f32, f32, f32)>,
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+#[derive(Asset, TypePath, Deserialize, Debug, Default)]
+#[serde(transparent)]
+pub(crate) struct StatusEffectLibrary(pub HashMap<String, StatusEffectDefinition>);
+
+#[derive(AssetCollection, Resource)]
+pub(crate) struct StatusEffectAssets {
+    #[asset(path = "data/status_effects.effects.ron")]
+    pub library: Handle<StatusEffectLibrary>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct ActiveStatusEffect {
+    pub id: String,
+    pub stacks: u32,
+    pub remaining: Timer,
+    pub tick: Option<Timer>,
+    definition: StatusEffectDefinition,
+}
+
+/// Timed effects currently applied to an entity, and the resulting modifiers
+#[derive(Component, Clone, Debug, Default)]
+pub(crate) struct StatusEffects {
+    pub active: Vec<ActiveStatusEffect>,
+}
+
+/// Gravity scale of the entity before any status effect, multiplied by the
+/// effects modifiers
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub(crate) struct BaseGravityScale(pub f32);
+
+/// Sprite color of the entity before any status effect, restored once the
+/// tints are gone
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub(crate) struct BaseColor(pub Color);
+
+impl StatusEffects {
+    pub fn has(&self, id: &str) -> bool {
+        self.active.iter().any(|effect| effect.id == id)
+    }
+
+    pub fn move_speed_multiplier(&self) -> f32 {
+        self.active
+            .iter()
+            .map(|effect| effect.definition.move_speed.powi(effect.stacks as i32))
+            .product()
+    }
+
+    pub fn damage_multiplier(&self) -> f32 {
+        self.active
+            .iter()
+            .map(|effect| effect.definition.damage.powi(effect.stacks as i32))
+            .product()
+    }
+
+    pub fn armor_bonus(&self) -> i32 {
+        self.active
+            .iter()
+            .map(|effect| effect.definition.armor * (effect.stacks as i32))
+            .sum()
+    }
+
+    pub fn gravity_scale_multiplier(&self) -> f32 {
+        self.active
+            .iter()
+            .map(|effect| effect.definition.gravity_scale.powi(effect.stacks as i32))
+            .product()
+    }
+
+    pub fn blocks_input(&self) -> bool {
+        self.active.iter().any(|effect| effect.definition.blocks_input)
+    }
+
+    /// The tint of the most recently applied effect having one
+    pub fn tint(&self) -> Option<Color> {
+        self.active
+            .iter()
+            .rev()
+            .find_map(|effect| effect.definition.tint)
+            .map(|(r, g, b, a)| Color::rgba(r, g, b, a))
+    }
+
+    /// Applies an effect, following the stacking rule of its definition
+    pub fn apply(&mut self, id: &str, definition: &StatusEffectDefinition) {
+        let duration = Duration::from_secs_f32(definition.duration);
+        if let Some(effect) = self.active.iter_mut().find(|effect| effect.id == id) {
+            match definition.stacking {
+                StatusEffectStacking::Ignore => {
+                    return;
+                }
+                StatusEffectStacking::Refresh => {}
+                StatusEffectStacking::Stack { max } => {
+                    effect.stacks = (effect.stacks + 1).min(max.max(1));
+                }
+            }
+            effect.remaining.set_duration(duration);
+            effect.remaining.reset();
+            return;
+        }
+        self.active.push(ActiveStatusEffect {
+            id: id.to_owned(),
+            stacks: 1,
+            remaining: Timer::new(duration, TimerMode::Once),
+            tick: definition.tick.map(|tick|
+                Timer::from_seconds(tick.interval, TimerMode::Repeating)
+            ),
+            definition: definition.clone(),
+        });
+    }
+
+    /// Ticks the effects timers, calling `on_tick` with the periodic effects
+    /// and how many times they apply (ticks times stacks). The expired effects
+    /// are removed, returns whether there were some.
+    pub fn tick(&mut self, delta: Duration, mut on_tick: impl FnMut(StatusTickKind, u32)) -> bool {
+        for effect in &mut self.active {
+            effect.remaining.tick(delta);
+            let (Some(timer), Some(tick)) = (&mut effect.tick, effect.definition.tick) else {
+                continue;
+            };
+            timer.tick(delta);
+            let amount = timer.times_finished_this_tick() * effect.stacks;
+            if amount > 0 {
+                on_tick(tick.kind, amount);
+            }
+        }
+        let count = self.active.len();
+        self.active.retain(|effect| !effect.remaining.finished());
+        self.active.len() != count
+    }
+}
+
+/// Request to apply the status effect `effect` (an id of the
+/// [`StatusEffectLibrary`]) to the `target` entity
+#[derive(Event, Clone, Debug)]
+pub(crate) struct ApplyStatusEffect {
+    pub target: Entity,
+    pub effect: String,
+}
+
+pub(crate) fn apply_status_effects(
+    mut commands: Commands,
+    mut events: EventReader<ApplyStatusEffect>,
+    mut query: Query<
+        (Option<&mut StatusEffects>, Option<&GravityScale>, Option<&Climber>, Option<&Sprite>)
+    >,
+    assets: Res<StatusEffectAssets>,
+    libraries: Res<Assets<StatusEffectLibrary>>
+) {
+    let Some(library) = libraries.get(&assets.library) else {
+        return;
+    };
+    for ApplyStatusEffect { target, effect } in events.read() {
+        let Some(definition) = library.0.get(effect) else {
+            warn!("Unknown status effect {:?}", effect);
+            continue;
+        };
+        match query.get_mut(*target) {
+            Ok((Some(mut status_effects), ..)) => status_effects.apply(effect, definition),
+            Ok((None, gravity_scale, climber, sprite)) => {
+                let mut status_effects = StatusEffects::default();
+                status_effects.apply(effect, definition);
+                let mut entity_commands = commands.entity(*target);
+                entity_commands.insert(status_effects);
+                if let Some(gravity_scale) = gravity_scale {
+                    // Climbing zeroes the gravity for a while, not for good
+                    let base = match climber {
+                        Some(Climber { climbing: true, .. }) => 1.0,
+                        _ => gravity_scale.0,
+                    };
+                    entity_commands.insert(BaseGravityScale(base));
+                }
+                if let Some(sprite) = sprite {
+                    entity_commands.insert(BaseColor(sprite.color));
+                }
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+/// Ticks the effects timers, sends the periodic damage and heal events and
+/// removes the expired effects, and the component along with the last one
+pub(crate) fn tick_status_effects(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut StatusEffects)>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut heal_events: EventWriter<HealEvent>
+) {
+    for (entity, mut status_effects) in &mut query {
+        // The timers tick every frame, only the changes of the effects
+        // themselves are reported to the `Changed<StatusEffects>` filters
+        let effects = status_effects.bypass_change_detection();
+        let expired = effects.tick(time.delta(), |kind, amount| {
+            match kind {
+                StatusTickKind::Damage(damage) => {
+                    damage_events.send(DamageEvent {
+                        target: entity,
+                        amount: damage * amount,
+                        source: None,
+                    });
+                }
+                StatusTickKind::Heal(heal) => {
+                    heal_events.send(HealEvent {
+                        target: entity,
+                        amount: heal * amount,
+                    });
+                }
+            }
+        });
+        if effects.active.is_empty() {
+            // The base gravity and color are restored on removal
+            commands.entity(entity).remove::<StatusEffects>();
+        } else if expired {
+            status_effects.set_changed();
+        }
+    }
+}
+
+/// Applies the gravity modifiers to entities that don't climb (climbers are
+/// handled by [`super::climbing::ignore_gravity_if_climbing`])
+pub(crate) fn apply_status_gravity(
+    mut query: Query<
+        (&StatusEffects, &BaseGravityScale, &mut GravityScale),
+        (Changed<StatusEffects>, Without<Climber>)
+    >,
+    mut removed: RemovedComponents<StatusEffects>,
+    mut restored: Query<(&BaseGravityScale, &mut GravityScale), Without<StatusEffects>>
+) {
+    for (status_effects, base, mut gravity_scale) in &mut query {
+        let scale = base.0 * status_effects.gravity_scale_multiplier();
+        if gravity_scale.0 != scale {
+            gravity_scale.0 = scale;
+        }
+    }
+    for entity in removed.read() {
+        if let Ok((base, mut gravity_scale)) = restored.get_mut(entity) {
+            gravity_scale.0 = base.0;
+        }
+    }
+}
+
+/// Visual hook of the status effects: tints the sprite of affected entities
+pub(crate) fn tint_status_effects(
+    mut query: Query<(&StatusEffects, &BaseColor, &mut Sprite), Changed<StatusEffects>>,
+    mut removed: RemovedComponents<StatusEffects>,
+    mut sprites: Query<(&BaseColor, &mut Sprite), Without<StatusEffects>>
+) {
+    for (status_effects, base, mut sprite) in &mut query {
+        let color = status_effects.tint().unwrap_or(base.0);
+        if sprite.color != color {
+            sprite.color = color;
+        }
+    }
+    for entity in removed.read() {
+        if let Ok((base, mut sprite)) = sprites.get_mut(entity) {
+            sprite.color = base.0;
+        }
+    }
+}
+
+pub(crate) fn plugin(app: &mut App) {
+    app.register_ron_asset::<StatusEffectLibrary>(&["effects.ron"])
+        .add_event::<ApplyStatusEffect>()
+        .configure_loading_state(
+            LoadingStateConfig::new(GameState::SplashScreen).load_collection::<StatusEffectAssets>()
+        )
+        .add_systems(
+            Update,
+            (
+                apply_status_effects,
+                tick_status_effects,
+                apply_status_gravity,
+                tint_status_effects,
+            )
+                .chain()
+                .before(super::health::apply_damage)
+                .before(super::health::apply_heal)
+                .run_if(in_state(GameState::Playing))
+        );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definition(stacking: StatusEffectStacking) -> StatusEffectDefinition {
+        StatusEffectDefinition {
+            duration: 6.0,
+            tick: Some(StatusTick { interval: 1.0, kind: StatusTickKind::Damage(2) }),
+            stacking,
+            move_speed: 0.5,
+            damage: 1.0,
+            armor: 0,
+            gravity_scale: 1.0,
+            blocks_input: false,
+            tint: None,
+        }
+    }
+
+    fn elapsed(status_effects: &StatusEffects) -> f32 {
+        status_effects.active[0].remaining.elapsed_secs()
+    }
+
+    fn tick(status_effects: &mut StatusEffects, seconds: f32) -> bool {
+        status_effects.tick(Duration::from_secs_f32(seconds), |_, _| {})
+    }
+
+    #[test]
+    fn refresh_restarts_the_duration() {
+        let definition = definition(StatusEffectStacking::Refresh);
+        let mut status_effects = StatusEffects::default();
+        status_effects.apply("poison", &definition);
+        tick(&mut status_effects, 4.0);
+        status_effects.apply("poison", &definition);
+        assert_eq!(status_effects.active.len(), 1);
+        assert_eq!(status_effects.active[0].stacks, 1);
+        assert_eq!(elapsed(&status_effects), 0.0);
+    }
+
+    #[test]
+    fn stack_adds_up_to_the_max() {
+        let definition = definition(StatusEffectStacking::Stack { max: 2 });
+        let mut status_effects = StatusEffects::default();
+        for _ in 0..3 {
+            status_effects.apply("slow", &definition);
+            tick(&mut status_effects, 1.0);
+        }
+        assert_eq!(status_effects.active.len(), 1);
+        assert_eq!(status_effects.active[0].stacks, 2);
+        assert_eq!(elapsed(&status_effects), 1.0);
+        assert_eq!(status_effects.move_speed_multiplier(), 0.25);
+    }
+
+    #[test]
+    fn ignore_keeps_the_running_effect() {
+        let definition = definition(StatusEffectStacking::Ignore);
+        let mut status_effects = StatusEffects::default();
+        status_effects.apply("freeze", &definition);
+        tick(&mut status_effects, 2.0);
+        status_effects.apply("freeze", &definition);
+        assert_eq!(status_effects.active[0].stacks, 1);
+        assert_eq!(elapsed(&status_effects), 2.0);
+    }
+
+    #[test]
+    fn effects_expire_after_their_duration() {
+        let mut status_effects = StatusEffects::default();
+        status_effects.apply("poison", &definition(StatusEffectStacking::Refresh));
+        assert!(!tick(&mut status_effects, 5.0));
+        assert!(status_effects.has("poison"));
+        assert!(tick(&mut status_effects, 1.0));
+        assert!(status_effects.active.is_empty());
+    }
+
+    #[test]
+    fn periodic_effects_tick_once_per_stack() {
+        let definition = definition(StatusEffectStacking::Stack { max: 5 });
+        let mut status_effects = StatusEffects::default();
+        status_effects.apply("poison", &definition);
+        status_effects.apply("poison", &definition);
+        let mut ticks = Vec::new();
+        status_effects.tick(Duration::from_secs_f32(2.0), |kind, amount| ticks.push((kind, amount)));
+        assert_eq!(ticks, vec![(StatusTickKind::Damage(2), 4)]);
+    }
+}
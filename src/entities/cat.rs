@@ -2,7 +2,7 @@ use bevy::{ ecs::{ bundle::Bundle, component::Component }, sprite::SpriteSheetBu
 use bevy_ecs_ldtk::prelude::LdtkEntity;
 
 use super::{ ColliderBundle, PredefinedPath };
-use crate::components::animals::Animal;
+use crate::components::animals::{ Animal, AnimalType };
 
 // 🐈‍⬛
 #[derive(Clone, Eq, PartialEq, Debug, Default, Component)]
@@ -10,15 +10,6 @@ pub struct Cat {
     name: String,
 }
 
-// Implement methods for the 'Cat' struct
-impl Cat {
-    // Define a new function that takes a String as an argument and returns a new instance of 'Cat'
-    fn new(name: String) -> Self {
-        // Return a new 'Cat' instance with the given name
-        Self { name }
-    }
-}
-
 // Implement the 'Animal' trait for the 'Cat' struct
 impl Animal for Cat {
     // Define the 'species' method to return the static string "Cat"
@@ -26,6 +17,17 @@ impl Animal for Cat {
         "Cat"
     }
 
+    // Names of 'Cat' instances are drawn from the 'Cat' names
+    fn animal_type() -> AnimalType {
+        AnimalType::Cat
+    }
+
+    // Define a new function that takes a String as an argument and returns a new instance of 'Cat'
+    fn new(name: String) -> Self {
+        // Return a new 'Cat' instance with the given name
+        Self { name }
+    }
+
     // Define the 'name' method to return a reference to the 'name' field of the 'Cat' instance
     fn name(&self) -> &String {
         &self.name
@@ -1,7 +1,26 @@
-use bevy::{ ecs::bundle::Bundle, sprite::SpriteSheetBundle };
-use bevy_ecs_ldtk::LdtkEntity;
+//! Cauldrons brewing [`Recipe`]s from the player inventory.
+//!
+//! [`Action::CycleRecipe`] selects the recipe of the closest cauldron,
+//! interacting with it while idle brews the selected recipe, or the first one
+//! the player has the ingredients for. Brewing goes on while the player is
+//! away, the result is collected by interacting again.
+//!
+//! The state of the cauldrons is kept by LDtk entity iid in [`Cauldrons`], so
+//! it survives the level being respawned.
 
-use super::ColliderBundle;
+use bevy::{ prelude::*, utils::HashMap };
+use bevy_ecs_ldtk::{ EntityInstance, LdtkEntity };
+use input_manager::action_state::ActionState;
+
+use super::{ ColliderBundle, Player };
+use crate::{
+    components::{
+        crafting::{ CraftingError, Recipe, RecipeAssets, RecipeBook },
+        interactions::{ InteractionSensor, Interactive },
+        items::{ Inventory, ItemId, ItemRegistry, Items },
+    },
+    plugins::{ dialogueview::not_in_dialogue, gamestate::GameState, input::Action },
+};
 
 #[derive(Clone, Default, Bundle, LdtkEntity)]
 pub struct CauldronBundle {
@@ -9,4 +28,174 @@ pub struct CauldronBundle {
     pub sprite_sheet_bundle: SpriteSheetBundle,
     #[from_entity_instance]
     pub collider_bundle: ColliderBundle,
+    #[from_entity_instance]
+    cauldron: Cauldron,
+}
+
+#[derive(Component, Clone, Debug, Default)]
+pub(crate) struct Cauldron {
+    /// Iid of the LDtk entity, its state in [`Cauldrons`]
+    pub iid: String,
+}
+
+impl From<&EntityInstance> for Cauldron {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        Self { iid: entity_instance.iid.clone() }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Brew {
+    pub recipe: String,
+    pub output: (ItemId, u32),
+    pub timer: Timer,
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CauldronState {
+    /// Id of the recipe chosen by the player
+    pub selected: Option<String>,
+    pub brewing: Option<Brew>,
+    /// Finished brew waiting to be collected
+    pub ready: Option<(ItemId, u32)>,
+}
+
+impl CauldronState {
+    pub fn start(
+        &mut self,
+        recipe: &Recipe,
+        inventory: &mut Inventory,
+        registry: &ItemRegistry
+    ) -> Result<(), CraftingError> {
+        if self.brewing.is_some() || self.ready.is_some() {
+            return Err(CraftingError::Busy);
+        }
+        recipe.consume(inventory, registry)?;
+        self.brewing = Some(Brew {
+            recipe: recipe.id.clone(),
+            output: recipe.output.clone(),
+            timer: Timer::from_seconds(recipe.brew_time, TimerMode::Once),
+        });
+        Ok(())
+    }
+}
+
+/// State of every cauldron, by LDtk entity iid
+#[derive(Resource, Clone, Debug, Default)]
+pub(crate) struct Cauldrons(pub HashMap<String, CauldronState>);
+
+/// Sent when a cauldron finishes brewing
+#[derive(Event, Clone, Debug)]
+pub(crate) struct BrewFinished {
+    /// Iid of the cauldron
+    pub cauldron: String,
+    pub recipe: String,
+}
+
+pub(crate) fn setup_cauldrons(mut commands: Commands, query: Query<Entity, Added<Cauldron>>) {
+    for entity in &query {
+        commands.entity(entity).insert(Interactive { name: "Cauldron".into() });
+    }
+}
+
+/// Uses the cauldron closest to a player: [`Action::CycleRecipe`] selects the
+/// next recipe, [`Action::Interact`] collects the finished brew, or starts a
+/// new one
+pub(crate) fn use_cauldrons(
+    sensors: Query<(&InteractionSensor, &Parent)>,
+    mut players: Query<(&ActionState<Action>, &mut Inventory), With<Player>>,
+    cauldrons: Query<&Cauldron>,
+    mut states: ResMut<Cauldrons>,
+    items: Items,
+    recipe_assets: Res<RecipeAssets>,
+    recipe_books: Res<Assets<RecipeBook>>
+) {
+    let (Some(registry), Some(recipe_book)) = (
+        items.registry(),
+        recipe_books.get(&recipe_assets.book),
+    ) else {
+        return;
+    };
+    for (sensor, parent) in &sensors {
+        let Ok((action_state, mut inventory)) = players.get_mut(**parent) else {
+            continue;
+        };
+        let cycle = action_state.just_pressed(&Action::CycleRecipe);
+        if !cycle && !action_state.just_pressed(&Action::Interact) {
+            continue;
+        }
+        let Some(Ok(cauldron)) = sensor.closest_entity.map(|entity| cauldrons.get(entity)) else {
+            continue;
+        };
+        let state = states.0.entry(cauldron.iid.clone()).or_default();
+
+        if cycle {
+            let next = recipe_book.next_after(state.selected.as_deref());
+            state.selected = next.map(|recipe| recipe.id.clone());
+            debug!("Selected recipe {:?}", state.selected);
+            continue;
+        }
+        if let Some((item, quantity)) = state.ready.clone() {
+            match inventory.add(registry, &item, quantity) {
+                Ok(()) => {
+                    state.ready = None;
+                }
+                Err(error) => info!("Could not collect {} {}: {}", quantity, item, error),
+            }
+            continue;
+        }
+        let recipe = match &state.selected {
+            Some(selected) => recipe_book.get(selected),
+            None => recipe_book.first_craftable(&inventory),
+        };
+        let result = recipe
+            .ok_or(CraftingError::NothingCraftable)
+            .and_then(|recipe| state.start(recipe, &mut inventory, registry));
+        match result {
+            Ok(()) => debug!("Started brewing {:?}", state.brewing),
+            Err(error) => info!("Could not brew: {}", error),
+        }
+    }
+}
+
+/// Brews in every cauldron, spawned or not
+pub(crate) fn brew(
+    time: Res<Time>,
+    mut states: ResMut<Cauldrons>,
+    mut finished_events: EventWriter<BrewFinished>
+) {
+    let mut finished = false;
+    // Ticking isn't a change worth reacting to, finishing is
+    for (iid, state) in states.bypass_change_detection().0.iter_mut() {
+        let Some(brew) = &mut state.brewing else {
+            continue;
+        };
+        if !brew.timer.tick(time.delta()).finished() {
+            continue;
+        }
+        let Some(brew) = state.brewing.take() else {
+            continue;
+        };
+        debug!("Cauldron {} finished brewing {}", iid, brew.recipe);
+        state.ready = Some(brew.output);
+        finished = true;
+        finished_events.send(BrewFinished {
+            cauldron: iid.clone(),
+            recipe: brew.recipe,
+        });
+    }
+    if finished {
+        states.set_changed();
+    }
+}
+
+pub(crate) fn plugin(app: &mut App) {
+    app.init_resource::<Cauldrons>()
+        .add_event::<BrewFinished>()
+        .add_systems(
+            Update,
+            (setup_cauldrons, use_cauldrons.run_if(not_in_dialogue), brew)
+                .chain()
+                .run_if(in_state(GameState::Playing))
+        );
 }
@@ -1,7 +1,35 @@
-use bevy::{ ecs::bundle::Bundle, sprite::SpriteSheetBundle };
-use bevy_ecs_ldtk::LdtkEntity;
+//! Chests opened by the player with [`Action::Interact`].
+//!
+//! The content comes from the LDtk `items` field and from the `loot_table`
+//! field, rolled as a [`LootSource::Container`]. Opened chests are remembered
+//! by LDtk entity iid in [`OpenedChests`], so they stay open when the level is
+//! respawned. They are saved to [`OPENED_CHESTS_PATH`] on every change, and
+//! read back when the game starts.
 
-use super::ColliderBundle;
+use std::{ fs, io, path::Path };
+
+use bevy::{ prelude::*, utils::HashSet };
+use bevy_ecs_ldtk::{ ldtk::ldtk_fields::LdtkFields, EntityInstance, LdtkEntity };
+use input_manager::action_state::ActionState;
+use rand::Rng;
+use serde::{ Deserialize, Serialize };
+use thiserror::Error;
+
+use super::{ ColliderBundle, Player };
+use crate::{
+    components::{
+        interactions::{ InteractionSensor, Interactive },
+        items::{ Inventory, ItemId, Items },
+        loot::{ Loot, LootAssets, LootSource, LootTables },
+        pickup::SpawnPickup,
+    },
+    plugins::{
+        dialogueview::not_in_dialogue,
+        entropy::{ EntropyAppExt, ForkedEntropy },
+        gamestate::GameState,
+        input::Action,
+    },
+};
 
 #[derive(Clone, Default, Bundle, LdtkEntity)]
 pub struct ChestBundle {
@@ -9,4 +37,214 @@ pub struct ChestBundle {
     pub sprite_sheet_bundle: SpriteSheetBundle,
     #[from_entity_instance]
     pub collider_bundle: ColliderBundle,
+    #[from_entity_instance]
+    pub chest: Chest,
+    pub inventory: Inventory,
+}
+
+#[derive(Component, Clone, Debug, Default)]
+pub struct Chest {
+    /// Iid of the LDtk entity, identifies the chest across respawns
+    pub iid: String,
+    pub opened: bool,
+}
+
+impl From<&EntityInstance> for Chest {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        Self {
+            iid: entity_instance.iid.clone(),
+            opened: false,
+        }
+    }
+}
+
+/// Save file of the [`OpenedChests`], relative to the working directory
+pub const OPENED_CHESTS_PATH: &str = "saves/opened_chests.ron";
+
+/// Iids of the chests opened by the player, part of the saved game state
+#[derive(Resource, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct OpenedChests(pub HashSet<String>);
+
+#[derive(Debug, Error)]
+pub(crate) enum SaveError {
+    #[error("could not access the save file: {0}")]
+    Io(#[from] io::Error),
+    #[error("could not parse the save file: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("could not serialize the save: {0}")]
+    Serialize(#[from] ron::Error),
+}
+
+impl OpenedChests {
+    /// Reads the saved chests, none if there is no save yet
+    pub(crate) fn load(path: impl AsRef<Path>) -> Result<Self, SaveError> {
+        match fs::read_to_string(path) {
+            Ok(content) => Ok(ron::from_str(&content)?),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    pub(crate) fn save(&self, path: impl AsRef<Path>) -> Result<(), SaveError> {
+        let path = path.as_ref();
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        let content = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        fs::write(path, content)?;
+        Ok(())
+    }
+}
+
+/// Plays the opening animation, up to the last frame of the sprite sheet
+#[derive(Component, Deref, DerefMut)]
+pub(crate) struct ChestOpening(Timer);
+
+/// Fills new chests, or shows them opened if they were before the respawn
+pub(crate) fn setup_chests(
+    mut commands: Commands,
+    mut query: Query<
+        (Entity, &mut Chest, &mut Inventory, &mut TextureAtlas, &EntityInstance),
+        Added<Chest>
+    >,
+    layouts: Res<Assets<TextureAtlasLayout>>,
+    opened_chests: Res<OpenedChests>,
+    items: Items
+) {
+    for (entity, mut chest, mut inventory, mut atlas, entity_instance) in &mut query {
+        if opened_chests.0.contains(&chest.iid) {
+            chest.opened = true;
+            if let Some(layout) = layouts.get(&atlas.layout) {
+                atlas.index = layout.len().saturating_sub(1);
+            }
+            continue;
+        }
+        commands.entity(entity).insert(Interactive { name: "Chest".into() });
+        let (Some(registry), Ok(ldtk_items)) = (
+            items.registry(),
+            entity_instance.iter_enums_field("items"),
+        ) else {
+            continue;
+        };
+        for ldtk_item in ldtk_items {
+            let item = ItemId::from(ldtk_item.as_str());
+            if let Err(error) = inventory.add(registry, &item, 1) {
+                warn!("Could not add LDtk item {} to chest {}: {}", ldtk_item, chest.iid, error);
+            }
+        }
+    }
+}
+
+/// Opens the chest closest to a player pressing [`Action::Interact`], moving
+/// its content into the player inventory. What doesn't fit is dropped.
+pub(crate) fn open_chests(
+    mut commands: Commands,
+    sensors: Query<(&InteractionSensor, &Parent)>,
+    mut players: Query<(&ActionState<Action>, &mut Inventory), (With<Player>, Without<Chest>)>,
+    mut chests: Query<(Entity, &mut Chest, &mut Inventory, &GlobalTransform, Option<&Loot>)>,
+    mut opened_chests: ResMut<OpenedChests>,
+    mut spawn_pickups: EventWriter<SpawnPickup>,
+    mut rng: ResMut<ForkedEntropy<Chest>>,
+    items: Items,
+    loot_assets: Res<LootAssets>,
+    loot_tables: Res<Assets<LootTables>>
+) {
+    let Some(registry) = items.registry() else {
+        return;
+    };
+    for (sensor, parent) in &sensors {
+        let Ok((action_state, mut player_inventory)) = players.get_mut(**parent) else {
+            continue;
+        };
+        if !action_state.just_pressed(&Action::Interact) {
+            continue;
+        }
+        let Some(Ok((entity, mut chest, mut inventory, transform, loot))) = sensor.closest_entity.map(
+            |entity| chests.get_mut(entity)
+        ) else {
+            continue;
+        };
+        if chest.opened {
+            continue;
+        }
+        chest.opened = true;
+        opened_chests.0.insert(chest.iid.clone());
+        commands
+            .entity(entity)
+            .remove::<Interactive>()
+            .insert(ChestOpening(Timer::from_seconds(0.1, TimerMode::Repeating)));
+        debug!("Opened chest {}", chest.iid);
+
+        let mut content = inventory.slots
+            .iter_mut()
+            .filter_map(Option::take)
+            .map(|stack| (stack.item, stack.quantity))
+            .collect::<Vec<_>>();
+        let loot_table = loot.and_then(|loot| {
+            loot_tables.get(&loot_assets.tables)?.0.get(&loot.table)
+        });
+        if let Some(loot_table) = loot_table {
+            content.extend(loot_table.roll(rng.as_mut(), LootSource::Container));
+        }
+        for (item, quantity) in content {
+            if let Err(error) = player_inventory.add(registry, &item, quantity) {
+                debug!("Dropping {} {} from chest {}: {}", quantity, item, chest.iid, error);
+                spawn_pickups.send(SpawnPickup {
+                    item,
+                    quantity,
+                    position: transform.translation().truncate(),
+                    velocity: Vec2::new(rng.gen_range(-60.0..60.0), 150.0),
+                    dropped_by: None,
+                });
+            }
+        }
+    }
+}
+
+pub(crate) fn animate_chest_opening(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut ChestOpening, &mut TextureAtlas)>,
+    layouts: Res<Assets<TextureAtlasLayout>>
+) {
+    for (entity, mut opening, mut atlas) in &mut query {
+        let last = layouts.get(&atlas.layout).map_or(0, |layout| layout.len().saturating_sub(1));
+        if atlas.index >= last {
+            commands.entity(entity).remove::<ChestOpening>();
+            continue;
+        }
+        if opening.tick(time.delta()).just_finished() {
+            atlas.index += 1;
+        }
+    }
+}
+
+/// Writes the [`OpenedChests`] once a chest is opened
+pub(crate) fn save_opened_chests(opened_chests: Res<OpenedChests>) {
+    if !opened_chests.is_changed() || opened_chests.is_added() {
+        return;
+    }
+    match opened_chests.save(OPENED_CHESTS_PATH) {
+        Ok(()) => debug!("Saved {} opened chests", opened_chests.0.len()),
+        Err(error) => error!("Could not save the opened chests: {}", error),
+    }
+}
+
+pub(crate) fn plugin(app: &mut App) {
+    let opened_chests = OpenedChests::load(OPENED_CHESTS_PATH).unwrap_or_else(|error| {
+        error!("Could not load the opened chests: {}", error);
+        OpenedChests::default()
+    });
+    app.insert_resource(opened_chests).init_forked_entropy::<Chest>().add_systems(
+        Update,
+        (
+            setup_chests,
+            open_chests.run_if(not_in_dialogue),
+            animate_chest_opening,
+            save_opened_chests,
+        )
+            .chain()
+            .before(crate::components::pickup::spawn_pickups)
+            .run_if(in_state(GameState::Playing))
+    );
 }
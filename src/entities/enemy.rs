@@ -1,20 +1,42 @@
 use bevy::{
+    app::{ App, Update },
     ecs::{
         bundle::Bundle,
         component::Component,
         entity::Entity,
-        system::{ In, Query, Res },
-        query::With,
+        event::EventReader,
+        schedule::{ common_conditions::in_state, IntoSystemConfigs },
+        system::{ Commands, In, Query, Res },
+        query::{ With, Without },
     },
+    hierarchy::DespawnRecursiveExt,
+    log,
+    math::Vec2,
+    reflect::Reflect,
     sprite::SpriteSheetBundle,
-    time::Time,
-    transform::components::Transform,
+    time::{ Time, Timer, TimerMode },
+    transform::components::{ GlobalTransform, Transform },
 };
-use bevy_ecs_ldtk::{ prelude::LdtkEntity, EntityInstance };
+use bevy_ecs_ldtk::{ ldtk::ldtk_fields::LdtkFields, prelude::LdtkEntity, EntityInstance };
+use bevy_rapier2d::{ dynamics::Velocity, geometry::Collider, plugin::RapierContext };
 use seldom_state::{ prelude::StateMachine, trigger::IntoTrigger as _ };
 
-use super::{ ColliderBundle, PredefinedPath };
-use crate::components::line_of_sight::LineOfSight;
+use super::{ ColliderBundle, PredefinedPath, Player };
+use crate::{
+    components::{
+        ai::{ movement::Busy, prediction::PlayerPrediction, ReflectAiComponent },
+        facing::Facing,
+        health::{ Died, Health },
+        hearing::Hearing,
+        line_of_sight::LineOfSight,
+        predefinedpath::PatrolSuspended,
+        shapeshift::Shapeshifted,
+    },
+    plugins::{
+        gamestate::GameState,
+        pathfinding::{ self, graph::EdgeKind, NavStep, PathRequest, PathResult, PathSearch },
+    },
+};
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
 pub struct Enemy;
@@ -26,91 +48,634 @@ pub struct EnemyBundle {
     #[from_entity_instance]
     pub collider_bundle: ColliderBundle,
     pub enemy: Enemy,
+    pub health: Health,
+    pub facing: Facing,
     #[ldtk_entity]
     pub predefined_path: PredefinedPath,
-    pub line_of_sight: LineOfSight<super::player::Player>,
+    #[with(make_line_of_sight)]
+    pub line_of_sight: LineOfSight<With<Player>>,
+    pub hearing: Hearing,
+    #[from_entity_instance]
+    pub locomotion: Locomotion,
     #[with(make_state_machine)]
     pub state_machine: StateMachine,
-    pub state: Idle,
+    pub state: Patrol,
 }
 
 //////////////////////////////////////////////////////////////////////////////////////////
 /// STATE
 
-fn make_state_machine(_: &EntityInstance) -> StateMachine {
-    // FIXME: should be adapted to use LineOfSight
-    let near_player = move |
-        In(entity): In<Entity>,
-        player_query: Query<(Entity, &Transform), With<super::Player>>,
-        transforms: Query<&Transform>
-    | {
-        let Ok((player, player_transform)) = player_query.get_single() else {
-            return Err(f32::INFINITY);
-        };
-        let distance = player_transform.translation
-            .truncate()
-            .distance(transforms.get(entity).unwrap().translation.truncate());
-
-        // Check whether the target is within range. If it is, return `Ok` to trigger!
-        match distance <= 300.0 {
-            true => Ok((distance, player)),
-            false => Err(distance),
-        }
+const CHASE_SPEED: f32 = 100.0;
+const SEARCH_SPEED: f32 = 40.0;
+const RETURN_SPEED: f32 = 30.0;
+/// How far from the last sighting the enemy looks around
+const SEARCH_RADIUS: f32 = 48.0;
+const SEARCH_DURATION: f32 = 6.0;
+/// Distance at which a point is considered reached
+const TOLERANCE: f32 = 4.0;
+
+/// Trigger firing with the player while the enemy sees them, otherwise
+/// failing with where they were last seen
+pub(crate) fn player_in_sight(
+    In(entity): In<Entity>,
+    observers: Query<&LineOfSight<With<Player>>>
+) -> Result<Entity, Option<Vec2>> {
+    let Ok(line_of_sight) = observers.get(entity) else {
+        return Err(None);
     };
+    match (line_of_sight.in_sight(), line_of_sight.tracked()) {
+        (true, Some(player)) => Ok(player),
+        _ => Err(line_of_sight.last_sighted()),
+    }
+}
+
+/// Trigger firing with the position of a noise heard during the last frame
+pub(crate) fn heard_noise(In(entity): In<Entity>, listeners: Query<&Hearing>) -> Result<Vec2, ()> {
+    listeners
+        .get(entity)
+        .ok()
+        .and_then(|hearing| hearing.heard)
+        .map(|heard| heard.position)
+        .ok_or(())
+}
+
+pub(crate) fn search_over(In(entity): In<Entity>, searches: Query<&Search>) -> bool {
+    searches.get(entity).is_ok_and(Search::is_over)
+}
+
+pub(crate) fn back_on_route(In(entity): In<Entity>, returns: Query<&Return>) -> bool {
+    returns.get(entity).is_ok_and(|state| state.arrived)
+}
+
+/// Vision of the enemy, from the optional LDtk `fov` (in degrees) and
+/// `sightRange` fields
+fn make_line_of_sight(entity_instance: &EntityInstance) -> LineOfSight<With<Player>> {
+    let mut line_of_sight = LineOfSight::default();
+    if let Ok(fov) = entity_instance.get_float_field("fov") {
+        line_of_sight = line_of_sight.with_cone(fov.to_radians(), (fov / 3.0).to_radians());
+    }
+    if let Ok(range) = entity_instance.get_float_field("sightRange") {
+        line_of_sight = line_of_sight.with_ranges(*range, range / 2.0);
+    }
+    line_of_sight
+}
 
+fn make_state_machine(_: &EntityInstance) -> StateMachine {
     // This state machine handles the enemy's transitions. Transitions defined earlier have
     // priority, but triggers after the first accepted one may still be checked.
     StateMachine::default()
-        // Add a transition. When they're in `Idle` state, and the `near_player` trigger occurs,
-        // switch to this instance of the `Follow` state
-        .trans_builder(
-            near_player,
-            // Transitions accept specific instances of states
-            |_old_state: &Idle, (_distance, target)| {
-                Some(Follow {
-                    target: target,
-                    speed: 100.0,
-                })
-            }
-        )
-        // Add a second transition. When they're in the `Follow` state, and the `near_player`
-        // trigger does not occur, switch to the `Idle` state. `.not()` is a combinator that
-        // negates the trigger. `.and(other)` and `.or(other)` also exist.
-        .trans::<Follow, _>(near_player.not(), Idle)
+        // Patrolling or looking for the player, chase them once in sight
+        .trans_builder(player_in_sight, |_: &Patrol, target| {
+            Some(Follow { target, speed: CHASE_SPEED })
+        })
+        .trans_builder(player_in_sight, |_: &Search, target| {
+            Some(Follow { target, speed: CHASE_SPEED })
+        })
+        .trans_builder(player_in_sight, |_: &Return, target| {
+            Some(Follow { target, speed: CHASE_SPEED })
+        })
+        // Lost sight of them, look around where they were last seen. `.not()`
+        // swaps the `Ok` and `Err` of the trigger.
+        .trans_builder(player_in_sight.not(), |_: &Follow, last_sighted| {
+            last_sighted.map(Search::around)
+        })
+        .trans::<Follow, _>(player_in_sight.not(), Return::default())
+        // Investigate the noises made out of sight
+        .trans_builder(heard_noise, |_: &Patrol, position| Some(Search::around(position)))
+        .trans_builder(heard_noise, |_: &Return, position| Some(Search::around(position)))
+        .trans_builder(heard_noise, |_: &Search, position| Some(Search::around(position)))
+        .trans::<Search, _>(search_over, Return::default())
+        .trans::<Return, _>(back_on_route, Patrol)
+        // Only patrolling enemies follow their path
+        .on_enter::<Patrol>(|entity| {
+            entity.remove::<PatrolSuspended>();
+        })
+        .on_exit::<Patrol>(|entity| {
+            entity.insert((PatrolSuspended, Velocity::zero()));
+        })
+        .on_exit::<Follow>(|entity| {
+            entity.remove::<(PathRequest, PathSearch, PathResult)>();
+        })
         // Enable transition logging
         .set_trans_logging(true)
 }
 
-// Entities in the `Idle` state do nothing
-#[derive(Clone, Component, Default)]
+/// Entities in the `Patrol` state follow their [`PredefinedPath`]
+#[derive(Clone, Component, Default, Reflect)]
 #[component(storage = "SparseSet")]
-pub struct Idle;
+#[reflect(AiComponent)]
+pub struct Patrol;
 
 // Entities in the `Follow` state move toward the given entity at the given speed
-#[derive(Clone, Component)]
+#[derive(Clone, Component, Reflect)]
 #[component(storage = "SparseSet")]
+#[reflect(AiComponent)]
 pub struct Follow {
     pub target: Entity,
     pub speed: f32,
 }
 
-// Let's define some behavior for entities in the follow state
-// FIXME: should be adapted to switch between patrol mode Idle state, and pathfinding follow during ()
+/// Entities in the `Search` state go back and forth around the place the player
+/// was last seen, or a noise was heard, until the timer runs out
+#[derive(Clone, Component, Reflect)]
+#[component(storage = "SparseSet")]
+#[reflect(AiComponent)]
+pub struct Search {
+    pub origin: Vec2,
+    pub direction: f32,
+    pub timer: Timer,
+}
+
+impl Search {
+    pub fn around(origin: Vec2) -> Self {
+        Self {
+            origin,
+            direction: 1.0,
+            timer: Timer::from_seconds(SEARCH_DURATION, TimerMode::Once),
+        }
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.timer.finished()
+    }
+
+    /// The point currently looked at
+    pub fn target(&self) -> Vec2 {
+        self.origin + Vec2::new(self.direction * SEARCH_RADIUS, 0.0)
+    }
+
+    /// Whether an enemy at `position` reached the point looked at, walking
+    /// ones only need to be above or below it
+    pub fn reached(&self, locomotion: Locomotion, position: Vec2) -> bool {
+        match locomotion {
+            Locomotion::Flying => self.target().distance(position) <= TOLERANCE,
+            Locomotion::Walking => (self.target().x - position.x).abs() <= TOLERANCE,
+        }
+    }
+}
+
+/// Entities in the `Return` state go back to the closest point of their
+/// [`PredefinedPath`]
+#[derive(Clone, Component, Default, Reflect)]
+#[component(storage = "SparseSet")]
+#[reflect(AiComponent)]
+pub struct Return {
+    pub arrived: bool,
+}
+
+/// How an enemy gets around, from the LDtk `locomotion` field
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Component)]
+pub enum Locomotion {
+    #[default]
+    Flying,
+    /// Bound to the ground, falls and jumps over gaps
+    Walking,
+}
+
+impl From<&EntityInstance> for Locomotion {
+    fn from(entity_instance: &EntityInstance) -> Self {
+        match entity_instance.get_enum_field("locomotion") {
+            Ok(value) if value == "Walking" => Self::Walking,
+            _ => Self::Flying,
+        }
+    }
+}
+
+/// How far ahead walls and ledges are looked for
+const LOOKAHEAD: f32 = 12.0;
+/// Height difference walked over without jumping
+const STEP: f32 = 6.0;
+const GRAVITY: f32 = 600.0;
+const MAX_FALL_SPEED: f32 = 400.0;
+const JUMP_SPEED: f32 = 220.0;
+/// Widest gap jumped over
+const JUMP_DISTANCE: f32 = 48.0;
+/// How far the target moves before a walking enemy looks for a new path
+const REPATH_DISTANCE: f32 = 16.0;
+/// How far ahead in time flying enemies aim at the player
+const LEAD_TIME: f32 = 0.3;
+/// A player who jumped this recently is chased to where they land
+const JUMP_WINDOW: f32 = 0.5;
+/// Longest fall of the player looked ahead
+const LANDING_HORIZON: f32 = 1.5;
+
+/// Velocity taking an enemy toward `target` without going through walls.
+///
+/// Enemies have kinematic bodies, so walking ones are given gravity here, and
+/// jump when `jump` is set, or over gaps when there is ground to land on.
+fn steer(
+    rapier_context: &RapierContext,
+    locomotion: Locomotion,
+    body: Body,
+    target: Vec2,
+    speed: f32,
+    jump: bool,
+    delta: f32
+) -> Vec2 {
+    let Body { position, half_height, velocity } = body;
+    let vector = target - position;
+    match locomotion {
+        Locomotion::Flying => {
+            let direction = vector.normalize_or_zero();
+            if !pathfinding::walkable(rapier_context, position, position + direction * LOOKAHEAD) {
+                return Vec2::ZERO;
+            }
+            direction * speed
+        }
+        Locomotion::Walking => {
+            let has_ground = |point: Vec2| {
+                pathfinding::ground_below(rapier_context, point, half_height + STEP).is_some()
+            };
+            if !has_ground(position) {
+                return fall(velocity, delta);
+            }
+            if vector.x.abs() <= TOLERANCE {
+                return Vec2::ZERO;
+            }
+            let direction = vector.x.signum();
+            if jump {
+                return Vec2::new(direction * speed, JUMP_SPEED);
+            }
+            let ahead = position + Vec2::new(direction * LOOKAHEAD, 0.0);
+            if !pathfinding::walkable(rapier_context, position, ahead) {
+                return Vec2::ZERO;
+            }
+            // Walk on, or drop down toward a target below
+            if has_ground(ahead) || vector.y < -half_height {
+                return Vec2::new(direction * speed, 0.0);
+            }
+            let landing = (1..=(JUMP_DISTANCE / LOOKAHEAD) as usize)
+                .map(|step| ahead + Vec2::new(direction * LOOKAHEAD * (step as f32), 0.0))
+                .take_while(|point| pathfinding::walkable(rapier_context, ahead, *point))
+                .any(has_ground);
+            match landing {
+                true => Vec2::new(direction * speed, JUMP_SPEED),
+                // Stay on the ledge
+                false => Vec2::ZERO,
+            }
+        }
+    }
+}
+
+/// Velocity of a walking enemy in the air, keeping its momentum
+fn fall(velocity: Vec2, delta: f32) -> Vec2 {
+    Vec2::new(velocity.x, (velocity.y - GRAVITY * delta).max(-MAX_FALL_SPEED))
+}
+
+/// Current state of the body of a moving enemy
+#[derive(Clone, Copy)]
+struct Body {
+    position: Vec2,
+    half_height: f32,
+    velocity: Vec2,
+}
+
+impl Body {
+    fn new(position: Vec2, collider: &Collider, velocity: &Velocity) -> Self {
+        Self {
+            position,
+            half_height: collider.as_cuboid().map_or(0.0, |cuboid| cuboid.half_extents().y),
+            velocity: velocity.linvel,
+        }
+    }
+}
+
+/// Keeps the path of walking enemies to the target they follow up to date
+pub fn request_chase_paths(
+    mut commands: Commands,
+    query: Query<
+        (Entity, &GlobalTransform, &Locomotion, &Follow, Option<&PathResult>),
+        (Without<PathRequest>, Without<PathSearch>, Without<Shapeshifted>, Without<Busy>)
+    >,
+    targets: Query<&GlobalTransform>,
+    prediction: PlayerPrediction
+) {
+    for (entity, transform, locomotion, follow, result) in &query {
+        if *locomotion != Locomotion::Walking {
+            continue;
+        }
+        let Ok(target) = targets.get(follow.target) else {
+            continue;
+        };
+        let mut target = target.translation().truncate();
+        // Head for where a jumping player comes down rather than where they are
+        if prediction.is_player(follow.target) && prediction.jumped_within(JUMP_WINDOW) {
+            target = prediction.landing_spot(LANDING_HORIZON).unwrap_or(target);
+        }
+        if result.is_some_and(|result| result.to.distance(target) <= REPATH_DISTANCE) {
+            continue;
+        }
+        commands.entity(entity).insert(PathRequest {
+            from: transform.translation().truncate(),
+            to: target,
+        });
+    }
+}
+
+/// The step of a path an enemy at `position` should head for
+fn next_step(steps: &[NavStep], position: Vec2) -> Option<NavStep> {
+    let closest = steps
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| {
+            a.position.distance_squared(position).total_cmp(&b.position.distance_squared(position))
+        })
+        .map(|(index, _)| index)?;
+    // Go on with the following one once there
+    match steps[closest].position.distance(position) <= TOLERANCE * 2.0 {
+        true => steps.get(closest + 1).or(steps.get(closest)).copied(),
+        false => Some(steps[closest]),
+    }
+}
+
+/// Moves the entities in the follow state toward their target, along their path
+/// if they have one, stopping if the target is gone
 pub fn follow(
-    mut transforms: Query<&mut Transform>,
-    follows: Query<(Entity, &Follow)>,
-    time: Res<Time>
+    time: Res<Time>,
+    mut query: Query<
+        (&GlobalTransform, &Collider, &Locomotion, &mut Velocity, &Follow, Option<&PathResult>),
+        (Without<Shapeshifted>, Without<Busy>)
+    >,
+    targets: Query<&GlobalTransform>,
+    rapier_context: Res<RapierContext>,
+    prediction: PlayerPrediction
+) {
+    for (transform, collider, locomotion, mut velocity, follow, result) in &mut query {
+        // The target may have despawned, the state machine moves on to
+        // searching once it's out of sight
+        let Ok(target) = targets.get(follow.target) else {
+            velocity.linvel = Vec2::ZERO;
+            continue;
+        };
+        let position = transform.translation().truncate();
+        let step = result
+            .and_then(|result| result.path.as_deref())
+            .and_then(|steps| next_step(steps, position));
+        let (waypoint, jump) = match step {
+            Some(step) => (step.position, step.kind == EdgeKind::Jump),
+            // Flying enemies cut the player off
+            None if *locomotion == Locomotion::Flying && prediction.is_player(follow.target) =>
+                (
+                    prediction
+                        .position_in(LEAD_TIME)
+                        .unwrap_or_else(|| target.translation().truncate()),
+                    false,
+                ),
+            None => (target.translation().truncate(), false),
+        };
+        velocity.linvel = steer(
+            &rapier_context,
+            *locomotion,
+            Body::new(position, collider, &velocity),
+            waypoint,
+            follow.speed,
+            jump,
+            time.delta_seconds()
+        );
+    }
+}
+
+pub fn search(
+    time: Res<Time>,
+    mut query: Query<
+        (Entity, &GlobalTransform, &Collider, &Locomotion, &mut Velocity, &mut Search),
+        (Without<Shapeshifted>, Without<Busy>)
+    >,
+    rapier_context: Res<RapierContext>
+) {
+    for (entity, transform, collider, locomotion, mut velocity, mut search) in &mut query {
+        search.timer.tick(time.delta());
+        let position = transform.translation().truncate();
+        let linvel = steer(
+            &rapier_context,
+            *locomotion,
+            Body::new(position, collider, &velocity),
+            search.target(),
+            SEARCH_SPEED,
+            false,
+            time.delta_seconds()
+        );
+        // Turn around once there, or when unable to go further
+        if search.reached(*locomotion, position) || linvel == Vec2::ZERO {
+            search.direction = -search.direction;
+            log::trace!("{:?} searching the other way", entity);
+        }
+        velocity.linvel = linvel;
+    }
+}
+
+pub fn return_to_route(
+    time: Res<Time>,
+    mut query: Query<
+        (
+            &Transform,
+            &GlobalTransform,
+            &Collider,
+            &Locomotion,
+            &mut Velocity,
+            &mut PredefinedPath,
+            &mut Return,
+        ),
+        (Without<Shapeshifted>, Without<Busy>)
+    >,
+    rapier_context: Res<RapierContext>
 ) {
-    for (entity, follow) in &follows {
-        // Get the positions of the follower and target
-        let target_translation = transforms.get(follow.target).unwrap().translation;
-        let follow_transform = &mut transforms.get_mut(entity).unwrap();
-        let follow_translation = follow_transform.translation;
-
-        // Find the direction from the follower to the target and go that way
-        follow_transform.translation +=
-            (target_translation - follow_translation).normalize_or_zero() *
-            follow.speed *
-            time.delta_seconds();
+    for (transform, global_transform, collider, locomotion, mut velocity, mut path, mut state) in &mut query {
+        if state.arrived {
+            continue;
+        }
+        // Path points are in the same space as the translation
+        let position = transform.translation.truncate();
+        let Some(index) = path.closest_point(position) else {
+            state.arrived = true;
+            continue;
+        };
+        let vector = path.points[index] - position;
+        if vector.length() > TOLERANCE {
+            let global_position = global_transform.translation().truncate();
+            velocity.linvel = steer(
+                &rapier_context,
+                *locomotion,
+                Body::new(global_position, collider, &velocity),
+                global_position + vector,
+                RETURN_SPEED,
+                false,
+                time.delta_seconds()
+            );
+            continue;
+        }
+        // Resume the patrol toward the next point
+        velocity.linvel = Vec2::ZERO;
+        resume_patrol(&mut path, index);
+        state.arrived = true;
+    }
+}
+
+/// Heads the patrol for the point following `index`, turning around at the
+/// ends of the path
+fn resume_patrol(path: &mut PredefinedPath, index: usize) {
+    if index == path.points.len() - 1 {
+        path.forward = false;
+    } else if index == 0 {
+        path.forward = true;
+    }
+    path.index = match path.forward {
+        true => (index + 1).min(path.points.len() - 1),
+        false => index.saturating_sub(1),
+    };
+}
+
+/// State machine of an enemy set aside while one of its AI actions runs
+#[derive(Component, Default)]
+pub(crate) struct SuspendedStateMachine(StateMachine);
+
+/// Sets the state machine of enemies made [`Busy`] by an action aside, its
+/// transitions would fight the action moves. The enemy stays in its state.
+pub(crate) fn suspend_state_machines(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut StateMachine), (With<Enemy>, With<Busy>)>
+) {
+    for (entity, mut state_machine) in &mut query {
+        log::debug!("{:?} suspends its state machine", entity);
+        let state_machine = std::mem::take(&mut *state_machine);
+        commands
+            .entity(entity)
+            .remove::<StateMachine>()
+            .insert(SuspendedStateMachine(state_machine));
+    }
+}
+
+/// Puts the state machine back once the action is over
+pub(crate) fn resume_state_machines(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut SuspendedStateMachine), Without<Busy>>
+) {
+    for (entity, mut suspended) in &mut query {
+        log::debug!("{:?} resumes its state machine", entity);
+        let state_machine = std::mem::take(&mut suspended.0);
+        commands.entity(entity).remove::<SuspendedStateMachine>().insert(state_machine);
+    }
+}
+
+/// Removes the enemies once their health drops to 0
+pub fn despawn_dead_enemies(
+    mut commands: Commands,
+    mut died_events: EventReader<Died>,
+    enemies: Query<(), With<Enemy>>
+) {
+    for Died { entity, .. } in died_events.read() {
+        if enemies.contains(*entity) {
+            commands.entity(*entity).despawn_recursive();
+        }
+    }
+}
+
+pub(crate) fn plugin(app: &mut App) {
+    app.register_type::<Patrol>()
+        .register_type::<Follow>()
+        .register_type::<Search>()
+        .register_type::<Return>()
+        .add_systems(
+            Update,
+            (
+                (suspend_state_machines, resume_state_machines),
+                request_chase_paths,
+                follow,
+                search,
+                return_to_route,
+            ).run_if(in_state(GameState::Playing))
+        );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn step(x: f32, kind: EdgeKind) -> NavStep {
+        NavStep { position: Vec2::new(x, 0.0), kind }
+    }
+
+    fn path(forward: bool) -> PredefinedPath {
+        PredefinedPath {
+            points: vec![Vec2::ZERO, Vec2::new(32.0, 0.0), Vec2::new(64.0, 0.0)],
+            index: 0,
+            forward,
+        }
+    }
+
+    #[test]
+    fn search_goes_back_and_forth() {
+        let mut search = Search::around(Vec2::new(100.0, 20.0));
+        assert_eq!(search.target(), Vec2::new(100.0 + SEARCH_RADIUS, 20.0));
+        search.direction = -search.direction;
+        assert_eq!(search.target(), Vec2::new(100.0 - SEARCH_RADIUS, 20.0));
+    }
+
+    #[test]
+    fn search_ends_with_its_timer() {
+        let mut search = Search::around(Vec2::ZERO);
+        assert!(!search.is_over());
+        search.timer.tick(Duration::from_secs_f32(SEARCH_DURATION / 2.0));
+        assert!(!search.is_over());
+        search.timer.tick(Duration::from_secs_f32(SEARCH_DURATION));
+        assert!(search.is_over());
+    }
+
+    #[test]
+    fn walking_searchers_only_need_to_be_above_the_target() {
+        let search = Search::around(Vec2::ZERO);
+        let above = search.target() + Vec2::new(1.0, 30.0);
+        assert!(search.reached(Locomotion::Walking, above));
+        assert!(!search.reached(Locomotion::Flying, above));
+        assert!(search.reached(Locomotion::Flying, search.target()));
+    }
+
+    #[test]
+    fn next_step_heads_for_the_closest_step() {
+        let steps = [step(0.0, EdgeKind::Walk), step(32.0, EdgeKind::Walk), step(64.0, EdgeKind::Jump)];
+        assert_eq!(next_step(&steps, Vec2::new(44.0, 0.0)), Some(steps[1]));
+    }
+
+    #[test]
+    fn next_step_moves_on_once_at_a_step() {
+        let steps = [step(0.0, EdgeKind::Walk), step(32.0, EdgeKind::Walk), step(64.0, EdgeKind::Jump)];
+        assert_eq!(next_step(&steps, Vec2::new(31.0, 0.0)), Some(steps[2]));
+        // The last step stays the goal
+        assert_eq!(next_step(&steps, Vec2::new(64.0, 0.0)), Some(steps[2]));
+        assert_eq!(next_step(&[], Vec2::ZERO), None);
+    }
+
+    #[test]
+    fn falling_keeps_momentum_up_to_the_max_speed() {
+        assert_eq!(fall(Vec2::new(10.0, 0.0), 0.1), Vec2::new(10.0, -GRAVITY * 0.1));
+        assert_eq!(fall(Vec2::new(-10.0, -MAX_FALL_SPEED), 0.1), Vec2::new(-10.0, -MAX_FALL_SPEED));
+    }
+
+    #[test]
+    fn steering_without_level_geometry() {
+        let rapier_context = RapierContext::default();
+        let body = Body { position: Vec2::ZERO, half_height: 8.0, velocity: Vec2::new(5.0, 0.0) };
+        let target = Vec2::new(30.0, 40.0);
+        // Flying enemies head straight for the target
+        let velocity = steer(&rapier_context, Locomotion::Flying, body, target, 50.0, false, 0.1);
+        assert!(velocity.abs_diff_eq(Vec2::new(30.0, 40.0), 1e-4));
+        // Walking ones fall with nothing below
+        let velocity = steer(&rapier_context, Locomotion::Walking, body, target, 50.0, false, 0.1);
+        assert_eq!(velocity, fall(body.velocity, 0.1));
+    }
+
+    #[test]
+    fn patrols_turn_around_at_the_ends() {
+        let mut route = path(true);
+        resume_patrol(&mut route, 1);
+        assert_eq!((route.index, route.forward), (2, true));
+        resume_patrol(&mut route, 2);
+        assert_eq!((route.index, route.forward), (1, false));
+        resume_patrol(&mut route, 1);
+        assert_eq!((route.index, route.forward), (0, false));
+        resume_patrol(&mut route, 0);
+        assert_eq!((route.index, route.forward), (1, true));
     }
 }
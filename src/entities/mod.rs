@@ -4,11 +4,13 @@ pub(crate) mod enemy;
 pub(crate) mod npc;
 pub(crate) mod pumpkin;
 pub(crate) mod chest;
+pub(crate) mod pickup;
 pub(crate) mod movingplatform;
 pub(crate) mod dog;
 pub(crate) mod cat;
 pub(crate) mod kade;
 pub(crate) mod cauldron;
+pub(crate) mod restspot;
 pub(crate) mod intcells;
 
 use bevy::app::App;
@@ -36,5 +38,8 @@ pub(super) fn plugin(app: &mut App) {
         .register_ldtk_entity::<movingplatform::MovingPlatformBundle>("MovingPlatform")
         .register_ldtk_entity::<enemy::EnemyBundle>("Enemy")
         .register_ldtk_entity::<chest::ChestBundle>("Chest")
-        .register_ldtk_entity::<pumpkin::PumpkinBundle>("Pumpkins");
+        .register_ldtk_entity::<pickup::PickupBundle>("Pickup")
+        .register_ldtk_entity::<pumpkin::PumpkinBundle>("Pumpkins")
+        .register_ldtk_entity::<restspot::RestSpotBundle>("RestSpot")
+        .add_plugins((chest::plugin, cauldron::plugin, enemy::plugin));
 }
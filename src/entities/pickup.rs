@@ -0,0 +1,16 @@
+use bevy::{ ecs::bundle::Bundle, sprite::SpriteSheetBundle };
+use bevy_ecs_ldtk::LdtkEntity;
+use bevy_rapier2d::dynamics::Velocity;
+
+use crate::components::pickup::{ Floating, Pickup };
+
+/// An item placed in the level, see [`crate::components::pickup`]
+#[derive(Clone, Default, Bundle, LdtkEntity)]
+pub struct PickupBundle {
+    #[sprite_sheet_bundle]
+    pub sprite_sheet_bundle: SpriteSheetBundle,
+    #[from_entity_instance]
+    pickup: Pickup,
+    floating: Floating,
+    velocity: Velocity,
+}
@@ -9,9 +9,12 @@ use crate::{
         health::Health,
         collision::ColliderBundle,
         ground::GroundDetection,
-        items::Items,
+        items::{ Inventory, LdtkItems },
+        equipment::{ BaseStats, Equipment, Stats },
         climbing::Climber,
         swimming::Swimmer,
+        facing::Facing,
+        hearing::Footsteps,
     },
     plugins::input,
     //plugins::input::{ ActionState, Slot, Ability, AbilitySlotMap },
@@ -34,10 +37,17 @@ pub struct PlayerBundle {
     pub ground_detection: GroundDetection,
     pub health: Health,
     pub armor: Armor,
+    pub facing: Facing,
+    pub footsteps: Footsteps,
 
-    // Build Items Component manually by using `impl From<&EntityInstance>`
+    // Build LdtkItems Component manually by using `impl From<&EntityInstance>`,
+    // the inventory is filled from it once the item registry is available
     #[from_entity_instance]
-    items: Items,
+    items: LdtkItems,
+    pub inventory: Inventory,
+    pub equipment: Equipment,
+    pub base_stats: BaseStats,
+    pub stats: Stats,
 
     // The whole EntityInstance can be stored directly as an EntityInstance component
     #[from_entity_instance]
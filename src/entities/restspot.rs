@@ -0,0 +1,11 @@
+use bevy::ecs::{ bundle::Bundle, component::Component };
+use bevy_ecs_ldtk::LdtkEntity;
+
+/// Place where NPCs go to sleep
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
+pub struct RestSpot;
+
+#[derive(Clone, Default, Bundle, LdtkEntity)]
+pub struct RestSpotBundle {
+    pub rest_spot: RestSpot,
+}
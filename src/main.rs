@@ -22,26 +22,6 @@ mod plugins;
 
 //use bevy_tweening::*;
 
-/*
-// 🧠
-use big_brain::{
-    prelude::{
-        ActionBuilder,
-        ActionState,
-        ActionSpan,
-        FirstToScore,
-        HasThinker,
-        Thinker,
-        ScorerBuilder,
-        Actor,
-        Score,
-        ScorerSpan,
-    },
-    BigBrainSet,
-    BigBrainPlugin,
-};
-*/
-
 // ✨ - ?
 // ✨ - Ray Marching
 /*
@@ -103,18 +83,7 @@ fn main() {
         // Plugins
         // 🎲
         .add_plugins(EntropyPlugin::<WyRand>::with_seed(seed.to_ne_bytes()))
-        // 🧠
-        /*
-        .add_plugins(BigBrainPlugin::new(PreUpdate))
-        .add_systems(Startup, components::ai::setup)
-        //.add_systems(Update, search_for_weapon)
-        //.add_systems(Update, drop_weapon)
-        .add_systems(PreUpdate, (
-            drink_action_system.in_set(BigBrainSet::Actions),
-            thirsty_scorer_system.in_set(BigBrainSet::Scorers),
-        ))
-        .add_systems(Update, thirst_system)
-		*/
+        // 🧠 - See components::ai::plugin
         // ~~~
         .add_plugins((
             DefaultPlugins.build()
@@ -136,6 +105,7 @@ fn main() {
             plugins::ui::plugin,
             plugins::audio::plugin,
             plugins::pathfinding::plugin,
+            plugins::clock::plugin,
             bevy_mod_aseprite::AsepritePlugin,
             StateMachinePlugin,
             //IncandescentPlugin,
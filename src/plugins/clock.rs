@@ -0,0 +1,171 @@
+//! In-game time of day.
+//!
+//! The [`GameClock`] runs while playing, stops during dialogues, and loops
+//! over days of [`MINUTES_PER_DAY`] game minutes.
+
+use std::{ fmt, str::FromStr };
+
+use bevy::prelude::*;
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::{ dialogueview::not_in_dialogue, gamestate::GameState };
+
+pub(crate) const MINUTES_PER_DAY: u32 = 24 * 60;
+/// Hour the sun rises
+const DAWN: u32 = 6;
+/// Hour the sun sets
+const DUSK: u32 = 20;
+
+/// A time of the day, written `HH:MM`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Reflect)]
+#[serde(try_from = "String")]
+pub struct ClockTime {
+    pub hour: u32,
+    pub minute: u32,
+}
+
+impl ClockTime {
+    pub fn new(hour: u32, minute: u32) -> Self {
+        Self { hour: hour % 24, minute: minute % 60 }
+    }
+
+    /// Minutes since midnight
+    pub fn minutes(&self) -> u32 {
+        self.hour * 60 + self.minute
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+#[error("invalid time {0:?}, expected HH:MM")]
+pub struct ClockTimeError(String);
+
+impl FromStr for ClockTime {
+    type Err = ClockTimeError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let error = || ClockTimeError(value.to_string());
+        let (hour, minute) = value.trim().split_once(':').ok_or_else(error)?;
+        let hour: u32 = hour.parse().map_err(|_| error())?;
+        let minute: u32 = minute.parse().map_err(|_| error())?;
+        if hour >= 24 || minute >= 60 {
+            return Err(error());
+        }
+        Ok(Self { hour, minute })
+    }
+}
+
+impl TryFrom<String> for ClockTime {
+    type Error = ClockTimeError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl fmt::Display for ClockTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.hour, self.minute)
+    }
+}
+
+#[derive(Resource, Clone, Debug, Reflect)]
+#[reflect(Resource)]
+pub struct GameClock {
+    /// Days started since the beginning of the game, from 1
+    pub day: u32,
+    /// Game minutes since midnight
+    minutes: f32,
+    /// Game minutes per real second
+    pub speed: f32,
+    pub paused: bool,
+}
+
+impl Default for GameClock {
+    fn default() -> Self {
+        Self {
+            day: 1,
+            minutes: (8 * 60) as f32,
+            // A day lasts 24 minutes
+            speed: 1.0,
+            paused: false,
+        }
+    }
+}
+
+impl GameClock {
+    pub fn time(&self) -> ClockTime {
+        let minutes = self.minutes as u32;
+        ClockTime::new(minutes / 60, minutes % 60)
+    }
+
+    pub fn is_night(&self) -> bool {
+        let hour = self.time().hour;
+        !(DAWN..DUSK).contains(&hour)
+    }
+}
+
+pub(crate) fn advance_clock(time: Res<Time>, mut clock: ResMut<GameClock>) {
+    if clock.paused {
+        return;
+    }
+    let was_night = clock.is_night();
+    clock.minutes += clock.speed * time.delta_seconds();
+    if clock.is_night() != was_night {
+        debug!("{} at {}", if was_night { "Dawn" } else { "Dusk" }, clock.time());
+    }
+    while clock.minutes >= (MINUTES_PER_DAY as f32) {
+        clock.minutes -= MINUTES_PER_DAY as f32;
+        clock.day += 1;
+        info!("Day {} begins", clock.day);
+    }
+}
+
+pub(crate) fn plugin(app: &mut App) {
+    app.init_resource::<GameClock>()
+        .register_type::<GameClock>()
+        .add_systems(
+            Update,
+            advance_clock.run_if(in_state(GameState::Playing).and_then(not_in_dialogue))
+        );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_clock_times() {
+        assert_eq!("08:30".parse::<ClockTime>(), Ok(ClockTime::new(8, 30)));
+        assert_eq!(" 0:05 ".parse::<ClockTime>(), Ok(ClockTime::new(0, 5)));
+        assert_eq!("23:59".parse::<ClockTime>(), Ok(ClockTime::new(23, 59)));
+    }
+
+    #[test]
+    fn rejects_invalid_clock_times() {
+        for value in ["", "8", "8h30", "24:00", "12:60", "-1:00", "aa:bb"] {
+            assert_eq!(value.parse::<ClockTime>(), Err(ClockTimeError(value.to_string())), "{value:?}");
+        }
+    }
+
+    #[test]
+    fn displays_clock_times() {
+        assert_eq!(ClockTime::new(7, 5).to_string(), "07:05");
+        assert_eq!("07:05".parse::<ClockTime>().unwrap().to_string(), "07:05");
+    }
+
+    #[test]
+    fn orders_clock_times() {
+        assert!(ClockTime::new(8, 59) < ClockTime::new(9, 0));
+        assert_eq!(ClockTime::new(1, 30).minutes(), 90);
+    }
+
+    #[test]
+    fn tells_night_from_day() {
+        let at = |hour: u32| GameClock { minutes: (hour * 60) as f32, ..default() };
+        assert!(at(DUSK).is_night());
+        assert!(at(DAWN - 1).is_night());
+        assert!(!at(DAWN).is_night());
+        assert!(!at(12).is_night());
+    }
+}
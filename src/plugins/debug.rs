@@ -14,6 +14,8 @@ use bevy_rapier2d::{ prelude::RapierDebugRenderPlugin, render::DebugRenderContex
 use super::{ gamestate::GameState, ui::fps_widget::{ spawn_fps_widget, FpsWidget } };
 use crate::components::childof::debug_children;
 
+mod ai;
+
 // Adds L key as debug KeyCode for toggling physics wireframes.
 pub fn toggle_physics_wireframes(
     mut ctx: ResMut<DebugRenderContext>,
@@ -33,6 +35,8 @@ pub(crate) fn plugin(app: &mut App) {
     app.add_plugins((
         // FpsWidget
         super::ui::fps_widget::plugin,
+        // AI debug overlay and thinkers panel
+        ai::plugin,
         // WorldInspectorPlugin
         WorldInspectorPlugin::default().run_if(input_toggle_active(false, KeyCode::F11)),
         // RapierDebugRenderPlugin
@@ -0,0 +1,228 @@
+//! AI debugging.
+//!
+//! F8 shows the live data of every AI actor above it: its scores, the action
+//! running and its [`ActionState`], its state machine state and what its
+//! [`LineOfSight`] sees. F7 opens a panel listing the thinkers, with their
+//! latest decisions.
+
+use std::{ collections::VecDeque, fmt::Write as _ };
+
+use bevy::{
+    ecs::{ archetype::Archetypes, component::Components, entity::Entities, system::SystemParam },
+    input::common_conditions::input_toggle_active,
+    prelude::*,
+    sprite::Anchor,
+    utils::HashMap,
+};
+use bevy_inspector_egui::bevy_egui::{ egui, EguiContexts };
+use big_brain::prelude::{ ActionState, Actor, HasThinker, Score, Thinker };
+use seldom_state::prelude::StateMachine;
+
+use crate::{
+    components::{ ai::ReflectAiComponent, line_of_sight::LineOfSight },
+    entities::Player,
+};
+
+/// Decisions kept per actor
+const HISTORY_LENGTH: usize = 20;
+const LABEL_HEIGHT: f32 = 28.0;
+
+/// Text showing the data of the AI actor it's a child of
+#[derive(Component)]
+struct AiDebugLabel;
+
+#[derive(Clone, Debug)]
+struct Decision {
+    /// Elapsed seconds
+    time: f32,
+    description: String,
+}
+
+/// Latest decisions of each actor
+#[derive(Resource, Default)]
+struct DecisionHistory {
+    actors: HashMap<Entity, VecDeque<Decision>>,
+    /// Last recorded state of each action
+    actions: HashMap<Entity, ActionState>,
+    /// Last recorded state machine state of each actor
+    states: HashMap<Entity, &'static str>,
+}
+
+impl DecisionHistory {
+    fn record(&mut self, actor: Entity, time: f32, description: String) {
+        let history = self.actors.entry(actor).or_default();
+        if history.len() >= HISTORY_LENGTH {
+            history.pop_front();
+        }
+        history.push_back(Decision { time, description });
+    }
+}
+
+/// Names the scorers, actions and states of the entities, from the components
+/// reflected with [`ReflectAiComponent`]
+#[derive(SystemParam)]
+struct AiComponentNames<'w> {
+    entities: &'w Entities,
+    archetypes: &'w Archetypes,
+    components: &'w Components,
+    registry: Res<'w, AppTypeRegistry>,
+}
+
+impl AiComponentNames<'_> {
+    /// The scorer or action of a scorer or action entity, or the state of an
+    /// actor
+    fn of(&self, entity: Entity) -> Option<&'static str> {
+        let location = self.entities.get(entity)?;
+        let registry = self.registry.read();
+        self.archetypes[location.archetype_id].components().find_map(|component| {
+            let type_id = self.components.get_info(component)?.type_id()?;
+            let registration = registry.get(type_id)?;
+            registration.data::<ReflectAiComponent>()?;
+            Some(registration.type_info().type_path_table().short_path())
+        })
+    }
+}
+
+fn record_decisions(
+    time: Res<Time>,
+    mut history: ResMut<DecisionHistory>,
+    names: AiComponentNames,
+    actions: Query<(Entity, &Actor, &ActionState)>,
+    state_machines: Query<Entity, With<StateMachine>>,
+    actors: Query<(), Or<(With<HasThinker>, With<StateMachine>)>>
+) {
+    let now = time.elapsed_seconds();
+    for (action, Actor(actor), state) in &actions {
+        if history.actions.get(&action) == Some(state) {
+            continue;
+        }
+        history.actions.insert(action, state.clone());
+        let description = format!("{}: {:?}", names.of(action).unwrap_or("Action"), state);
+        history.record(*actor, now, description);
+    }
+    for actor in &state_machines {
+        let Some(state) = names.of(actor) else {
+            continue;
+        };
+        if history.states.insert(actor, state) != Some(state) {
+            history.record(actor, now, format!("State: {}", state));
+        }
+    }
+    // Forget the despawned actions and actors
+    history.actions.retain(|action, _| actions.contains(*action));
+    history.states.retain(|actor, _| actors.contains(*actor));
+    history.actors.retain(|actor, _| actors.contains(*actor));
+}
+
+fn update_labels(
+    mut commands: Commands,
+    names: AiComponentNames,
+    actors: Query<
+        (Entity, Option<&LineOfSight<With<Player>>>),
+        Or<(With<HasThinker>, With<StateMachine>)>
+    >,
+    scorers: Query<(Entity, &Actor, &Score)>,
+    actions: Query<(Entity, &Actor, &ActionState)>,
+    mut labels: Query<(&Parent, &mut Text), With<AiDebugLabel>>,
+    asset_server: Res<AssetServer>
+) {
+    let mut texts: HashMap<Entity, String> = HashMap::new();
+    for (scorer, Actor(actor), score) in &scorers {
+        let text = texts.entry(*actor).or_default();
+        let _ = writeln!(text, "{}: {:.2}", names.of(scorer).unwrap_or("Scorer"), score.get());
+    }
+    for (action, Actor(actor), state) in &actions {
+        let text = texts.entry(*actor).or_default();
+        let _ = writeln!(text, "> {} ({:?})", names.of(action).unwrap_or("Action"), state);
+    }
+    for (actor, line_of_sight) in &actors {
+        let text = texts.entry(actor).or_default();
+        if let Some(state) = names.of(actor) {
+            let _ = writeln!(text, "State: {}", state);
+        }
+        if let Some(line_of_sight) = line_of_sight {
+            let _ = writeln!(
+                text,
+                "Sight: {:?} {:.0}%{}",
+                line_of_sight.tracked(),
+                line_of_sight.awareness() * 100.0,
+                if line_of_sight.in_sight() { " (in sight)" } else { "" }
+            );
+        }
+    }
+
+    for (parent, mut text) in &mut labels {
+        if let Some(value) = texts.remove(&**parent) {
+            text.sections[0].value = value;
+        }
+    }
+    // Actors without a label yet
+    for (actor, value) in texts {
+        if !actors.contains(actor) {
+            continue;
+        }
+        let text = Text::from_section(value, TextStyle {
+            font: asset_server.load("fonts/bahnschrift.ttf"),
+            font_size: 8.0,
+            color: Color::YELLOW,
+        });
+        commands.entity(actor).with_children(|builder| {
+            builder.spawn((
+                AiDebugLabel,
+                Name::new("AiDebugLabel"),
+                Text2dBundle {
+                    text,
+                    text_anchor: Anchor::BottomCenter,
+                    transform: Transform::from_xyz(0.0, LABEL_HEIGHT, 10.0),
+                    ..default()
+                },
+            ));
+        });
+    }
+}
+
+fn remove_labels(mut commands: Commands, labels: Query<Entity, With<AiDebugLabel>>) {
+    for label in &labels {
+        commands.entity(label).despawn_recursive();
+    }
+}
+
+fn thinker_panel(
+    mut contexts: EguiContexts,
+    thinkers: Query<(Entity, &Actor), With<Thinker>>,
+    names: Query<&Name>,
+    history: Res<DecisionHistory>
+) {
+    egui::Window::new("Thinkers").show(contexts.ctx_mut(), |ui| {
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for (thinker, Actor(actor)) in &thinkers {
+                let name = names.get(*actor).map_or("Actor", |name| name.as_str());
+                ui.collapsing(format!("{} {:?} ({:?})", name, actor, thinker), |ui| {
+                    let Some(decisions) = history.actors.get(actor) else {
+                        ui.label("No decision yet");
+                        return;
+                    };
+                    for decision in decisions.iter().rev() {
+                        ui.label(format!("{:>8.2}s  {}", decision.time, decision.description));
+                    }
+                });
+            }
+        });
+    });
+}
+
+pub(crate) fn plugin(app: &mut App) {
+    app.init_resource::<DecisionHistory>().add_systems(
+        Update,
+        (
+            record_decisions,
+            update_labels.run_if(input_toggle_active(false, KeyCode::F8)),
+            remove_labels.run_if(
+                not(input_toggle_active(false, KeyCode::F8)).and_then(
+                    any_with_component::<AiDebugLabel>
+                )
+            ),
+            thinker_panel.run_if(input_toggle_active(false, KeyCode::F7)),
+        ).chain()
+    );
+}
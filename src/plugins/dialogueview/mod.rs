@@ -74,7 +74,10 @@ struct Assets {
 impl<T: States> Plugin for YarnSpinnerDialogueViewPlugin<T> {
     fn build(&self, app: &mut App) {
         app.add_plugins(
-            YarnSpinnerPlugin::with_yarn_source(YarnFileSource::file("dialogues/test_dialog.yarn"))
+            YarnSpinnerPlugin::with_yarn_sources([
+                YarnFileSource::file("dialogues/test_dialog.yarn"),
+                YarnFileSource::file("dialogues/animals.yarn"),
+            ])
         )
             .configure_loading_state(
                 LoadingStateConfig::new(self.loading_state.clone()).load_collection::<Assets>()
@@ -96,8 +99,7 @@ impl<T: States> Plugin for YarnSpinnerDialogueViewPlugin<T> {
 fn spawn_dialogue_runner(mut commands: Commands, project: Res<YarnProject>) {
     info!("Starting dialogue runner.");
     // Create a dialogue runner from the project.
-    let mut dialogue_runner = project.create_dialogue_runner();
-    // ⚠️ TODO: Only run dialogues on interaction!
-    //dialogue_runner.start_node("Start");
+    let dialogue_runner = project.create_dialogue_runner();
+    // Dialogues are started on interaction, from the `ActiveDialogue` of NPCs
     commands.spawn(dialogue_runner);
 }
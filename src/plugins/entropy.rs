@@ -0,0 +1,47 @@
+use std::marker::PhantomData;
+
+use bevy::{ app::App, ecs::system::Resource };
+use bevy_rand::prelude::{ EntropyComponent, ForkableRng, GlobalEntropy, WyRand };
+use rand::RngCore;
+
+/// Random number generator of a single consumer, `T` being its marker.
+///
+/// It's forked from the seeded [`GlobalEntropy`] when its plugin is built, in
+/// the order the plugins are added. Draws of a consumer then don't depend on
+/// the order the systems sharing the global one would run in.
+#[derive(Resource)]
+pub(crate) struct ForkedEntropy<T> {
+    rng: EntropyComponent<WyRand>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> RngCore for ForkedEntropy<T> {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+pub(crate) trait EntropyAppExt {
+    /// Inserts a [`ForkedEntropy<T>`], the `EntropyPlugin` has to be added
+    /// first.
+    fn init_forked_entropy<T: 'static>(&mut self) -> &mut Self;
+}
+
+impl EntropyAppExt for App {
+    fn init_forked_entropy<T: 'static>(&mut self) -> &mut Self {
+        let rng = self.world.resource_mut::<GlobalEntropy<WyRand>>().fork_rng();
+        self.insert_resource(ForkedEntropy::<T> { rng, marker: PhantomData })
+    }
+}
@@ -26,13 +26,24 @@ pub use input_manager::Actionlike;
 use std::collections::HashMap;
 use std::time::Instant;
 
-use crate::components::{ climbing::Climber, ground::GroundDetection, swimming::Swimmer };
+use crate::components::{
+    climbing::Climber,
+    equipment::Stats,
+    ground::GroundDetection,
+    hitbox::MeleeAttack,
+    status_effects::StatusEffects,
+    swimming::Swimmer,
+};
 
 #[derive(Actionlike, PartialEq, Eq, Hash, Clone, Copy, Debug, Reflect)]
 pub enum Action {
     Move,
     Jump,
     Interact,
+    /// Cycles the command given to the pets
+    CommandPet,
+    /// Selects the next recipe of a cauldron
+    CycleRecipe,
     Ability(u8),
 }
 
@@ -95,6 +106,8 @@ pub(crate) fn make_action_map<Ability: Actionlike>(
     let input_map = InputMap::new([
         (Action::Jump, KeyCode::Space),
         (Action::Interact, KeyCode::KeyE),
+        (Action::CommandPet, KeyCode::KeyF),
+        (Action::CycleRecipe, KeyCode::KeyG),
         (Action::PRIMARY_ACTION, KeyCode::KeyQ),
         (Action::SECONDARY_ACTION, KeyCode::KeyW),
         (Action::Ability(2), KeyCode::KeyR),
@@ -106,6 +119,8 @@ pub(crate) fn make_action_map<Ability: Actionlike>(
     ])
         .with(Action::Move, dual_axis_pad)
         .with(Action::Interact, GamepadButtonType::RightTrigger2)
+        .with(Action::CommandPet, GamepadButtonType::DPadDown)
+        .with(Action::CycleRecipe, GamepadButtonType::DPadRight)
         .with(Action::Jump, MouseButton::Left)
         .with(Action::Jump, GamepadButtonType::LeftTrigger)
         .with(Action::PRIMARY_ACTION, MouseButton::Right)
@@ -147,6 +162,9 @@ pub(crate) fn movement(
             &mut Climber,
             &mut Swimmer,
             &GroundDetection,
+            Option<&StatusEffects>,
+            Option<&Stats>,
+            Option<&MeleeAttack>,
         )
     >,
     time: Res<Time<Real>>
@@ -158,7 +176,23 @@ pub(crate) fn movement(
         mut climber,
         mut swimmer,
         ground_detection,
+        status_effects,
+        stats,
+        melee_attack,
     ) in &mut query {
+        // Frozen, stunned, winding up or recovering from an attack...
+        let blocked =
+            status_effects.is_some_and(StatusEffects::blocks_input) ||
+            melee_attack.is_some_and(MeleeAttack::is_committed);
+        if blocked {
+            velocity.linvel.x = 0.0;
+            continue;
+        }
+        // Stats already account for the status effects
+        let speed_multiplier = match stats {
+            Some(stats) => stats.move_speed,
+            None => status_effects.map_or(1.0, StatusEffects::move_speed_multiplier),
+        };
         if
             let Some(ActionData { axis_pair: Some(axis_pair), state, .. }) =
                 action_state.action_data(&Action::Move)
@@ -170,7 +204,8 @@ pub(crate) fn movement(
             }
 
             swimmer.swimming = !swimmer.intersecting_swimmables.is_empty();
-            let axis_gain = if swimmer.swimming { AXIS_GAIN * 0.5 } else { AXIS_GAIN };
+            let axis_gain =
+                speed_multiplier * (if swimmer.swimming { AXIS_GAIN * 0.5 } else { AXIS_GAIN });
 
             velocity.linvel.x = axis_pair.x() * axis_gain;
             if climber.climbing {
@@ -205,15 +240,23 @@ pub(crate) fn movement(
 
 fn copy_ability_action_state<Ability: Actionlike + Copy + Clone + std::fmt::Debug>(
     mut query: Query<
-        (&mut ActionState<Action>, &mut ActionState<Ability>, &AbilitySlotMap<Ability>)
+        (
+            &mut ActionState<Action>,
+            &mut ActionState<Ability>,
+            &AbilitySlotMap<Ability>,
+            Option<&MeleeAttack>,
+        )
     >
 ) {
-    for (mut action_state, mut ability_state, ability_slot_map) in query.iter_mut() {
+    for (mut action_state, mut ability_state, ability_slot_map, melee_attack) in query.iter_mut() {
+        // Attacks can't be cancelled outside of their active frames
+        let committed = melee_attack.is_some_and(MeleeAttack::is_committed);
         for (&slot, &ability) in ability_slot_map.map.iter() {
-            ability_state.set_action_data(
-                ability,
-                action_state.action_data_mut_or_default(&Action::Ability(slot)).clone()
-            );
+            let action_data = match committed {
+                true => ActionData::default(),
+                false => action_state.action_data_mut_or_default(&Action::Ability(slot)).clone(),
+            };
+            ability_state.set_action_data(ability, action_data);
         }
     }
 }
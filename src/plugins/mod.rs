@@ -8,7 +8,10 @@ pub(crate) mod easing;
 pub(crate) mod get_backend;
 pub(crate) mod rapier_utils;
 pub(crate) mod audio;
+pub(crate) mod clock;
+pub(crate) mod entropy;
 pub(crate) mod pathfinding;
+pub(crate) mod ron_asset;
 
 #[cfg(feature="dev_features")]
 pub(crate) mod debug;
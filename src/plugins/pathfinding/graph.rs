@@ -0,0 +1,412 @@
+//! Navigation graph of a level for ground-based agents.
+//!
+//! Nodes are the cells an agent can occupy: standing on solid ground or on top
+//! of a ladder, holding on a ladder, or swimming. Edges link them with the move
+//! needed to go from one to the other and its cost.
+
+use std::{ cmp::Ordering, collections::BinaryHeap };
+
+use bevy::{ math::{ IVec2, Vec2 }, utils::HashMap };
+use bevy_ecs_ldtk::ldtk::LayerInstance;
+
+/// Highest jump, in cells
+const JUMP_HEIGHT: i32 = 2;
+/// Widest jump, in cells
+const JUMP_WIDTH: i32 = 3;
+/// Longest fall taken on purpose, in cells
+const MAX_DROP: i32 = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Cell {
+    Empty,
+    Wall,
+    Ladder,
+    Water,
+}
+
+impl Cell {
+    /// From the values of the `Collisions` IntGrid, see `entities::plugin`
+    fn from_int_grid(value: i32) -> Self {
+        match value {
+            1 | 3 => Self::Wall,
+            2 => Self::Ladder,
+            4 => Self::Water,
+            _ => Self::Empty,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum EdgeKind {
+    Walk,
+    Jump,
+    Drop,
+    Climb,
+    Swim,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Edge {
+    pub to: usize,
+    pub kind: EdgeKind,
+    pub cost: f32,
+}
+
+/// One step of a path, the move reaching a position
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct NavStep {
+    pub position: Vec2,
+    pub kind: EdgeKind,
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct NavGraph {
+    /// World position of the bottom left corner of the level
+    origin: Vec2,
+    grid_size: f32,
+    size: IVec2,
+    cells: Vec<Cell>,
+    /// Cell of each node
+    nodes: Vec<IVec2>,
+    node_by_cell: HashMap<IVec2, usize>,
+    edges: Vec<Vec<Edge>>,
+}
+
+impl NavGraph {
+    /// Builds the graph of a level from its IntGrid layer, `origin` being the
+    /// world position of the level
+    pub fn from_layer(layer: &LayerInstance, origin: Vec2) -> Self {
+        Self::from_cells(
+            IVec2::new(layer.c_wid, layer.c_hei),
+            layer.grid_size as f32,
+            origin,
+            layer.int_grid_csv.iter().map(|value| Cell::from_int_grid(*value))
+        )
+    }
+
+    /// Builds the graph of a grid of `size` cells, given row by row from the
+    /// top one like an IntGrid
+    fn from_cells(
+        size: IVec2,
+        grid_size: f32,
+        origin: Vec2,
+        rows: impl IntoIterator<Item = Cell>
+    ) -> Self {
+        // The IntGrid goes from the top row, the graph from the bottom one
+        let mut cells = vec![Cell::Empty; (size.x * size.y) as usize];
+        for (index, value) in rows.into_iter().enumerate() {
+            let index = index as i32;
+            let cell = IVec2::new(index % size.x, size.y - 1 - index / size.x);
+            cells[(cell.y * size.x + cell.x) as usize] = value;
+        }
+        let mut graph = Self {
+            origin,
+            grid_size,
+            size,
+            cells,
+            ..Default::default()
+        };
+        graph.add_nodes();
+        graph.add_edges();
+        graph
+    }
+
+    fn cell(&self, cell: IVec2) -> Cell {
+        if cell.x < 0 || cell.y < 0 || cell.x >= self.size.x || cell.y >= self.size.y {
+            // Out of the level is as good as a wall
+            return Cell::Wall;
+        }
+        self.cells[(cell.y * self.size.x + cell.x) as usize]
+    }
+
+    fn is_free(&self, cell: IVec2) -> bool {
+        self.cell(cell) != Cell::Wall
+    }
+
+    /// Standing on solid ground or on top of a ladder
+    fn is_standable(&self, cell: IVec2) -> bool {
+        let below = self.cell(cell - IVec2::Y);
+        matches!(self.cell(cell), Cell::Empty | Cell::Ladder) &&
+            (below == Cell::Wall || (below == Cell::Ladder && self.cell(cell) == Cell::Empty))
+    }
+
+    fn is_node(&self, cell: IVec2) -> bool {
+        self.is_standable(cell) || matches!(self.cell(cell), Cell::Ladder | Cell::Water)
+    }
+
+    fn add_nodes(&mut self) {
+        for y in 0..self.size.y {
+            for x in 0..self.size.x {
+                let cell = IVec2::new(x, y);
+                if self.is_node(cell) {
+                    self.node_by_cell.insert(cell, self.nodes.len());
+                    self.nodes.push(cell);
+                }
+            }
+        }
+        self.edges = vec![Vec::new(); self.nodes.len()];
+    }
+
+    fn add_edge(&mut self, from: usize, to: IVec2, kind: EdgeKind, cost: f32) {
+        if let Some(&to) = self.node_by_cell.get(&to) {
+            if to != from {
+                self.edges[from].push(Edge { to, kind, cost });
+            }
+        }
+    }
+
+    fn add_edges(&mut self) {
+        for from in 0..self.nodes.len() {
+            let cell = self.nodes[from];
+            let kind = self.cell(cell);
+            let standable = self.is_standable(cell);
+            for direction in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+                let neighbour = cell + direction;
+                if !self.node_by_cell.contains_key(&neighbour) {
+                    continue;
+                }
+                let neighbour_kind = self.cell(neighbour);
+                if kind == Cell::Water || neighbour_kind == Cell::Water {
+                    self.add_edge(from, neighbour, EdgeKind::Swim, 2.0);
+                } else if kind == Cell::Ladder || neighbour_kind == Cell::Ladder {
+                    // Onto, off and along ladders
+                    self.add_edge(from, neighbour, EdgeKind::Climb, 1.5);
+                } else if direction.y == 0 && standable && self.is_standable(neighbour) {
+                    self.add_edge(from, neighbour, EdgeKind::Walk, 1.0);
+                }
+            }
+            if standable {
+                self.add_drops(from, cell);
+                self.add_jumps(from, cell);
+            }
+        }
+    }
+
+    /// Walking off a ledge and falling onto whatever is below
+    fn add_drops(&mut self, from: usize, cell: IVec2) {
+        for side in [-1, 1] {
+            let start = cell + IVec2::new(side, 0);
+            if !self.is_free(start) || self.node_by_cell.contains_key(&start) {
+                continue;
+            }
+            let landing = (1..=MAX_DROP)
+                .map(|fall| start - IVec2::new(0, fall))
+                .take_while(|below| self.is_free(*below))
+                .find(|below| self.node_by_cell.contains_key(below));
+            if let Some(landing) = landing {
+                // Never less than the distance, the A* heuristic
+                let cost = (landing - cell).as_vec2().length();
+                self.add_edge(from, landing, EdgeKind::Drop, cost);
+            }
+        }
+    }
+
+    /// Jumping to a standable cell, following a rough arc: up from the start,
+    /// across at the apex, then down onto the landing
+    fn add_jumps(&mut self, from: usize, cell: IVec2) {
+        for dx in (-JUMP_WIDTH..=JUMP_WIDTH).filter(|dx| *dx != 0) {
+            for dy in -JUMP_HEIGHT..=JUMP_HEIGHT {
+                // Plain walking
+                if dx.abs() == 1 && dy == 0 {
+                    continue;
+                }
+                let landing = cell + IVec2::new(dx, dy);
+                if !self.is_standable(landing) {
+                    continue;
+                }
+                let apex = cell.y + dy.max(0) + 1;
+                let rises = (cell.y..=apex).all(|y| self.is_free(IVec2::new(cell.x, y)));
+                let crosses = (cell.x.min(landing.x)..=cell.x.max(landing.x)).all(|x|
+                    self.is_free(IVec2::new(x, apex))
+                );
+                let lands = (landing.y..=apex).all(|y| self.is_free(IVec2::new(landing.x, y)));
+                if rises && crosses && lands {
+                    let cost = (1.0 + (dx.abs() as f32) + (dy.max(0) as f32) * 2.0).max(
+                        (landing - cell).as_vec2().length()
+                    );
+                    self.add_edge(from, landing, EdgeKind::Jump, cost);
+                }
+            }
+        }
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.iter().map(Vec::len).sum()
+    }
+
+    /// Whether the position is inside the level
+    pub fn contains(&self, position: Vec2) -> bool {
+        let cell = self.cell_at(position);
+        cell.x >= 0 && cell.y >= 0 && cell.x < self.size.x && cell.y < self.size.y
+    }
+
+    fn cell_at(&self, position: Vec2) -> IVec2 {
+        ((position - self.origin) / self.grid_size).floor().as_ivec2()
+    }
+
+    /// World position of the center of a cell
+    fn position(&self, cell: IVec2) -> Vec2 {
+        self.origin + (cell.as_vec2() + Vec2::splat(0.5)) * self.grid_size
+    }
+
+    /// The node an agent at `position` is on: its cell, or the closest one
+    /// below or around it
+    fn node_at(&self, position: Vec2) -> Option<usize> {
+        let cell = self.cell_at(position);
+        (0..=2)
+            .map(|fall| cell - IVec2::new(0, fall))
+            .find_map(|cell| self.node_by_cell.get(&cell).copied())
+            .or_else(|| {
+                (-2..=2)
+                    .flat_map(|x| (-2..=2).map(move |y| cell + IVec2::new(x, y)))
+                    .filter_map(|cell| self.node_by_cell.get(&cell).copied())
+                    .min_by_key(|node| (self.nodes[*node] - cell).length_squared())
+            })
+    }
+
+    /// Cheapest path between two world positions, as the steps following the
+    /// start, with A*
+    pub fn find_path(&self, from: Vec2, to: Vec2) -> Option<Vec<NavStep>> {
+        if !self.contains(to) {
+            return None;
+        }
+        let start = self.node_at(from)?;
+        let goal = self.node_at(to)?;
+        let heuristic = |node: usize| self.nodes[node].as_vec2().distance(self.nodes[goal].as_vec2());
+
+        let mut costs = vec![f32::INFINITY; self.nodes.len()];
+        let mut came_from: Vec<Option<(usize, EdgeKind)>> = vec![None; self.nodes.len()];
+        let mut open = BinaryHeap::new();
+        costs[start] = 0.0;
+        open.push(Candidate { node: start, estimate: heuristic(start) });
+
+        while let Some(Candidate { node, estimate }) = open.pop() {
+            if node == goal {
+                let mut steps = Vec::new();
+                let mut current = goal;
+                while let Some((previous, kind)) = came_from[current] {
+                    steps.push(NavStep { position: self.position(self.nodes[current]), kind });
+                    current = previous;
+                }
+                steps.reverse();
+                return Some(steps);
+            }
+            // Outdated entry, the node was reached cheaper since
+            if estimate > costs[node] + heuristic(node) {
+                continue;
+            }
+            for edge in &self.edges[node] {
+                let cost = costs[node] + edge.cost;
+                if cost < costs[edge.to] {
+                    costs[edge.to] = cost;
+                    came_from[edge.to] = Some((node, edge.kind));
+                    open.push(Candidate { node: edge.to, estimate: cost + heuristic(edge.to) });
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Entry of the A* open set, ordered so the heap pops the lowest estimate first
+#[derive(Clone, Copy, PartialEq)]
+struct Candidate {
+    node: usize,
+    estimate: f32,
+}
+
+impl Eq for Candidate {}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRID_SIZE: f32 = 16.0;
+
+    /// A graph from rows of `#` walls, `H` ladders, `~` water and `.` empty
+    /// cells, the top row first
+    fn graph(rows: &[&str]) -> NavGraph {
+        let size = IVec2::new(rows[0].len() as i32, rows.len() as i32);
+        let cells = rows.iter().flat_map(|row| {
+            row.chars().map(|cell| {
+                match cell {
+                    '#' => Cell::Wall,
+                    'H' => Cell::Ladder,
+                    '~' => Cell::Water,
+                    _ => Cell::Empty,
+                }
+            })
+        });
+        NavGraph::from_cells(size, GRID_SIZE, Vec2::ZERO, cells)
+    }
+
+    fn position(x: i32, y: i32) -> Vec2 {
+        (IVec2::new(x, y).as_vec2() + Vec2::splat(0.5)) * GRID_SIZE
+    }
+
+    fn kinds(steps: &[NavStep]) -> Vec<EdgeKind> {
+        steps.iter().map(|step| step.kind).collect()
+    }
+
+    #[test]
+    fn walks_along_the_floor() {
+        let graph = graph(&[".....", ".....", "#####"]);
+        let steps = graph.find_path(position(0, 1), position(4, 1)).unwrap();
+        assert_eq!(kinds(&steps), vec![EdgeKind::Walk; 4]);
+        assert_eq!(steps.last().unwrap().position, position(4, 1));
+    }
+
+    #[test]
+    fn jumps_onto_a_step() {
+        let graph = graph(&[".....", ".....", ".....", "...##", "#####"]);
+        let steps = graph.find_path(position(0, 1), position(4, 2)).unwrap();
+        assert!(kinds(&steps).contains(&EdgeKind::Jump));
+        assert_eq!(steps.last().unwrap().position, position(4, 2));
+    }
+
+    #[test]
+    fn drops_off_a_ledge() {
+        // The ceiling leaves no room to jump down instead
+        let graph = graph(&["###..", ".....", "##...", "##...", "#####"]);
+        let steps = graph.find_path(position(0, 3), position(4, 1)).unwrap();
+        assert_eq!(kinds(&steps), vec![
+            EdgeKind::Walk,
+            EdgeKind::Drop,
+            EdgeKind::Walk,
+            EdgeKind::Walk,
+        ]);
+    }
+
+    #[test]
+    fn no_path_through_walls() {
+        let graph = graph(&["..#..", "..#..", "#####"]);
+        assert_eq!(graph.find_path(position(0, 1), position(4, 1)), None);
+    }
+
+    #[test]
+    fn edges_cost_at_least_the_distance() {
+        let graph = graph(&["......", "......", "##....", "##..##", "######"]);
+        for (from, edges) in graph.edges.iter().enumerate() {
+            for edge in edges {
+                let distance = graph.nodes[from].as_vec2().distance(graph.nodes[edge.to].as_vec2());
+                assert!(edge.cost >= distance, "{:?} from {:?} is too cheap", edge, graph.nodes[from]);
+            }
+        }
+    }
+}
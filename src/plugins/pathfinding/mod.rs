@@ -1,61 +1,126 @@
+//! 🧭 Pathfinding
+//!
+//! A [`NavGraph`] is built for every spawned level, from its `Collisions`
+//! IntGrid, and queried through [`NavGraphs`].
+
+pub(crate) mod graph;
+mod requests;
+
+use std::sync::Arc;
+
 use bevy::{
     app::{ App, Update },
     asset::{ Assets, Handle },
-    ecs::{ query::Added, system::{ Query, Res } },
+    ecs::{
+        event::EventReader,
+        schedule::IntoSystemConfigs,
+        system::{ Query, Res, ResMut, Resource },
+    },
     log,
+    math::Vec2,
+    transform::components::Transform,
+    utils::HashMap,
 };
-use bevy_ecs_ldtk::{ assets::LdtkProject, prelude::RawLevelAccessor };
+use bevy_ecs_ldtk::{ assets::LdtkProject, prelude::RawLevelAccessor, LevelEvent, LevelIid };
+use bevy_rapier2d::{ pipeline::QueryFilter, plugin::RapierContext };
+
+pub(crate) use graph::{ NavGraph, NavStep };
+pub(crate) use requests::{ PathCache, PathRequest, PathResult, PathSearch, PathfindingBudget };
 
 pub fn plugin(app: &mut App) {
-    app.add_systems(Update, process_ldtk_project);
+    app.init_resource::<NavGraphs>()
+        .init_resource::<PathCache>()
+        .init_resource::<PathfindingBudget>()
+        .add_systems(
+            Update,
+            (
+                rebuild_nav_graphs,
+                requests::clear_path_cache,
+                requests::start_path_searches,
+                requests::finish_path_searches,
+            ).chain()
+        );
+}
+
+/// Navigation graphs of the spawned levels
+#[derive(Resource, Default)]
+pub(crate) struct NavGraphs {
+    levels: HashMap<LevelIid, Arc<NavGraph>>,
+}
+
+impl NavGraphs {
+    /// The graph of the level containing a world position
+    pub fn graph_at(&self, position: Vec2) -> Option<&Arc<NavGraph>> {
+        self.levels.values().find(|graph| graph.contains(position))
+    }
+}
+
+/// Whether an actor can go straight from `from` to `to` without running into
+/// level geometry
+pub(crate) fn walkable(rapier_context: &RapierContext, from: Vec2, to: Vec2) -> bool {
+    let vector = to - from;
+    let distance = vector.length();
+    if distance <= f32::EPSILON {
+        return true;
+    }
+    rapier_context
+        .cast_ray(from, vector / distance, distance, true, QueryFilter::only_fixed().exclude_sensors())
+        .is_none()
+}
+
+/// Distance to the level geometry right below `from`, within `max_distance`
+pub(crate) fn ground_below(
+    rapier_context: &RapierContext,
+    from: Vec2,
+    max_distance: f32
+) -> Option<f32> {
+    rapier_context
+        .cast_ray(from, Vec2::NEG_Y, max_distance, true, QueryFilter::only_fixed().exclude_sensors())
+        .map(|(_, toi)| toi)
 }
 
-/// Converts the int cell layer of the levels into representation for pathfinder (WIP)
-pub(crate) fn process_ldtk_project(
-    ldtk_project_handle_query: Query<&Handle<LdtkProject>, Added<Handle<LdtkProject>>>,
-    ldtk_project_assets: Res<Assets<LdtkProject>>
+/// Builds the navigation graph of the levels once they are placed in the world,
+/// on load and after a `Respawn`, and forgets the despawned ones
+pub(crate) fn rebuild_nav_graphs(
+    mut level_events: EventReader<LevelEvent>,
+    levels: Query<(&LevelIid, &Transform)>,
+    ldtk_projects: Query<&Handle<LdtkProject>>,
+    ldtk_project_assets: Res<Assets<LdtkProject>>,
+    mut nav_graphs: ResMut<NavGraphs>
 ) {
-    let Ok(ldtk_project_handle) = ldtk_project_handle_query.get_single() else {
-        return;
-    };
-    let ldtk_project = ldtk_project_assets
-        .get(ldtk_project_handle)
-        .expect("Project should be loaded if level has spawned");
-    for level in ldtk_project.iter_raw_levels() {
-        log::debug!("level {} ({})", level.identifier, level.iid);
-        let Some(layers) = &level.layer_instances else {
-            continue;
-        };
-        const LKT: &[u8] = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ".as_bytes();
-        for layer in layers {
-            let layer_string = if !layer.int_grid_csv.is_empty() {
-                format!(
-                    ":\n{}",
-                    layer.int_grid_csv
-                        .chunks(layer.c_wid as usize)
-                        .map(|c|
-                            String::from_utf8(
-                                c
-                                    .iter()
-                                    .map(|i| LKT[*i as usize])
-                                    .collect::<Vec<_>>()
-                            ).unwrap()
-                        )
-                        .collect::<Vec<_>>()
-                        .join("\n")
-                )
-            } else {
-                String::new()
-            };
-            log::debug!(
-                "layer {} ({}) {}x{} v:{} {}",
-                layer.identifier,
-                layer.iid,
-                layer.c_wid,
-                layer.c_hei,
-                layer.visible,
-                layer_string
-            );
+    for event in level_events.read() {
+        match event {
+            LevelEvent::Transformed(iid) => {
+                let Some(ldtk_project) = ldtk_projects
+                    .get_single()
+                    .ok()
+                    .and_then(|handle| ldtk_project_assets.get(handle)) else {
+                    continue;
+                };
+                let Some((_, transform)) = levels.iter().find(|(level_iid, _)| *level_iid == iid) else {
+                    continue;
+                };
+                let layer = ldtk_project
+                    .get_raw_level_by_iid(iid.get())
+                    .and_then(|level| level.layer_instances.as_ref())
+                    .and_then(|layers| layers.iter().find(|layer| layer.identifier == "Collisions"));
+                let Some(layer) = layer else {
+                    log::warn!("No Collisions layer to build the navigation graph of level {}", iid);
+                    continue;
+                };
+                let graph = NavGraph::from_layer(layer, transform.translation.truncate());
+                log::debug!(
+                    "Navigation graph of level {}: {} nodes, {} edges",
+                    iid,
+                    graph.node_count(),
+                    graph.edge_count()
+                );
+                nav_graphs.levels.insert(iid.clone(), Arc::new(graph));
+            }
+            LevelEvent::Despawned(iid) => {
+                nav_graphs.levels.remove(iid);
+            }
+            _ => {}
         }
     }
 }
@@ -0,0 +1,188 @@
+//! Path requests, searched in the background.
+//!
+//! Insert a [`PathRequest`] on an agent, a [`PathResult`] replaces it once the
+//! search is done. Searches run on the [`AsyncComputeTaskPool`], at most
+//! [`PathfindingBudget::per_frame`] of them are collected each frame, and
+//! recent results are reused from the [`PathCache`].
+//!
+//! A pending search lives in a component of the agent: inserting a new request
+//! or despawning the agent drops it, which cancels the task.
+
+use bevy::{
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::Changed,
+        system::{ Commands, Query, Res, ResMut, Resource },
+    },
+    log,
+    math::{ IVec2, Vec2 },
+    tasks::{ block_on, futures_lite::future, AsyncComputeTaskPool, Task },
+    time::Time,
+    utils::HashMap,
+};
+
+use super::{ NavGraphs, NavStep };
+
+/// Size of the squares positions are rounded to when looking up the cache
+const CACHE_GRID: f32 = 16.0;
+
+/// Asks for a path between two world positions
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub(crate) struct PathRequest {
+    pub from: Vec2,
+    pub to: Vec2,
+}
+
+/// Outcome of a [`PathRequest`], `path` is `None` when `to` can't be reached
+#[derive(Component, Clone, Debug, PartialEq)]
+pub(crate) struct PathResult {
+    pub to: Vec2,
+    pub path: Option<Vec<NavStep>>,
+}
+
+/// Search running for the [`PathRequest`] of an entity
+#[derive(Component)]
+pub(crate) struct PathSearch {
+    key: (IVec2, IVec2),
+    to: Vec2,
+    task: Task<Option<Vec<NavStep>>>,
+}
+
+#[derive(Resource, Clone, Copy, Debug)]
+pub(crate) struct PathfindingBudget {
+    /// Searches collected per frame, the others wait for the next frames
+    pub per_frame: usize,
+}
+
+impl Default for PathfindingBudget {
+    fn default() -> Self {
+        Self { per_frame: 8 }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct CachedPath {
+    path: Option<Vec<NavStep>>,
+    /// Elapsed seconds when the path was found
+    found_at: f32,
+}
+
+/// Recently found paths, by rounded start and end positions
+#[derive(Resource, Debug)]
+pub(crate) struct PathCache {
+    entries: HashMap<(IVec2, IVec2), CachedPath>,
+    pub capacity: usize,
+    /// Seconds before a path is searched again
+    pub lifetime: f32,
+}
+
+impl Default for PathCache {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            capacity: 256,
+            lifetime: 5.0,
+        }
+    }
+}
+
+impl PathCache {
+    fn key(from: Vec2, to: Vec2) -> (IVec2, IVec2) {
+        (
+            (from / CACHE_GRID).floor().as_ivec2(),
+            (to / CACHE_GRID).floor().as_ivec2(),
+        )
+    }
+
+    fn get(&self, key: &(IVec2, IVec2), now: f32) -> Option<&Option<Vec<NavStep>>> {
+        self.entries
+            .get(key)
+            .filter(|cached| now - cached.found_at <= self.lifetime)
+            .map(|cached| &cached.path)
+    }
+
+    fn insert(&mut self, key: (IVec2, IVec2), path: Option<Vec<NavStep>>, now: f32) {
+        if self.entries.len() >= self.capacity {
+            let lifetime = self.lifetime;
+            self.entries.retain(|_, cached| now - cached.found_at <= lifetime);
+        }
+        if self.entries.len() >= self.capacity {
+            let oldest = self.entries
+                .iter()
+                .min_by(|(_, a), (_, b)| a.found_at.total_cmp(&b.found_at))
+                .map(|(key, _)| *key);
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(key, CachedPath { path, found_at: now });
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// Cached paths are outdated once the graphs change
+pub(crate) fn clear_path_cache(nav_graphs: Res<NavGraphs>, mut cache: ResMut<PathCache>) {
+    if nav_graphs.is_changed() {
+        cache.clear();
+    }
+}
+
+pub(crate) fn start_path_searches(
+    mut commands: Commands,
+    time: Res<Time>,
+    requests: Query<(Entity, &PathRequest), Changed<PathRequest>>,
+    nav_graphs: Res<NavGraphs>,
+    cache: Res<PathCache>
+) {
+    let now = time.elapsed_seconds();
+    for (entity, request) in &requests {
+        let key = PathCache::key(request.from, request.to);
+        let mut entity_commands = commands.entity(entity);
+        if let Some(path) = cache.get(&key, now) {
+            entity_commands
+                .remove::<(PathRequest, PathSearch)>()
+                .insert(PathResult { to: request.to, path: path.clone() });
+            continue;
+        }
+        let Some(graph) = nav_graphs.graph_at(request.from).cloned() else {
+            log::trace!("No navigation graph at {} for {:?}", request.from, entity);
+            entity_commands
+                .remove::<(PathRequest, PathSearch)>()
+                .insert(PathResult { to: request.to, path: None });
+            continue;
+        };
+        let PathRequest { from, to } = *request;
+        let task = AsyncComputeTaskPool::get().spawn(async move { graph.find_path(from, to) });
+        // Replaces, and so cancels, the previous search of the entity
+        entity_commands.insert(PathSearch { key, to, task });
+    }
+}
+
+pub(crate) fn finish_path_searches(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut searches: Query<(Entity, &mut PathSearch)>,
+    budget: Res<PathfindingBudget>,
+    mut cache: ResMut<PathCache>
+) {
+    let now = time.elapsed_seconds();
+    let mut finished = 0;
+    for (entity, mut search) in &mut searches {
+        if finished >= budget.per_frame {
+            break;
+        }
+        let Some(path) = block_on(future::poll_once(&mut search.task)) else {
+            continue;
+        };
+        finished += 1;
+        cache.insert(search.key, path.clone(), now);
+        commands
+            .entity(entity)
+            .remove::<(PathRequest, PathSearch)>()
+            .insert(PathResult { to: search.to, path });
+    }
+}
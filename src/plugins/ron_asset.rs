@@ -0,0 +1,69 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    app::App,
+    asset::{ io::Reader, Asset, AssetApp, AssetLoader, AsyncReadExt, LoadContext },
+    utils::BoxedFuture,
+};
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+
+/// Asset loader deserializing any [`Asset`] from a RON file.
+///
+/// Game data assets use double extensions (`*.effects.ron`, `*.items.ron`…)
+/// so every asset type gets its own loader.
+struct RonAssetLoader<A> {
+    extensions: &'static [&'static str],
+    marker: PhantomData<fn() -> A>,
+}
+
+#[derive(Debug, Error)]
+pub(crate) enum RonAssetLoaderError {
+    #[error("could not read the asset: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse the RON asset: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+impl<A: Asset + DeserializeOwned> AssetLoader for RonAssetLoader<A> {
+    type Asset = A;
+    type Settings = ();
+    type Error = RonAssetLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext
+    ) -> BoxedFuture<'a, Result<A, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            Ok(ron::de::from_bytes::<A>(&bytes)?)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        self.extensions
+    }
+}
+
+pub(crate) trait RonAssetAppExt {
+    /// Registers the asset type `A` and a RON loader for the given extensions.
+    fn register_ron_asset<A: Asset + DeserializeOwned>(
+        &mut self,
+        extensions: &'static [&'static str]
+    ) -> &mut Self;
+}
+
+impl RonAssetAppExt for App {
+    fn register_ron_asset<A: Asset + DeserializeOwned>(
+        &mut self,
+        extensions: &'static [&'static str]
+    ) -> &mut Self {
+        self.init_asset::<A>().register_asset_loader(RonAssetLoader::<A> {
+            extensions,
+            marker: PhantomData,
+        })
+    }
+}
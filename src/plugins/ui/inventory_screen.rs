@@ -0,0 +1,523 @@
+//! Inventory and equipment screen.
+//!
+//! Opened and closed with [`InventoryAction::Toggle`] (I, Tab or the gamepad
+//! select button). The slots are navigated with the arrows, the D-pad or the
+//! mouse; the selected item is equipped/used with Enter, A or a click, and
+//! dropped with X or the gamepad west button.
+//!
+//! Gameplay is paused while the screen is open: virtual time and physics are
+//! stopped and the player actions are consumed.
+
+use bevy::{ prelude::*, ui::FocusPolicy };
+use bevy_rapier2d::plugin::RapierConfiguration;
+use input_manager::{
+    action_state::ActionState,
+    input_map::InputMap,
+    plugin::{ InputManagerPlugin, InputManagerSystem },
+    Actionlike,
+};
+use sickle_ui::{
+    ui_builder::{ UiBuilder, UiBuilderExt, UiRoot },
+    ui_commands::SetTextExt,
+    widgets::{ container::UiContainerExt, label::{ LabelConfig, UiLabelExt } },
+};
+
+use crate::{
+    components::{
+        equipment::{ Equipment, EquipmentSlot },
+        facing::Facing,
+        items::{ Inventory, ItemDefinition, ItemId, ItemRegistry, Items, UseItem },
+        pickup::SpawnPickup,
+    },
+    entities::Player,
+    plugins::{ dialogueview::not_in_dialogue, gamestate::GameState, input::Action },
+};
+
+const COLUMNS: usize = 6;
+const SLOT_SIZE: f32 = 40.0;
+const FONT: &str = "fonts/bahnschrift.ttf";
+const SLOT_COLOR: Color = Color::rgba(0.2, 0.2, 0.2, 0.9);
+const SELECTED_SLOT_COLOR: Color = Color::rgba(0.6, 0.5, 0.2, 0.9);
+
+#[derive(Actionlike, PartialEq, Eq, Hash, Clone, Copy, Debug, Reflect)]
+pub(crate) enum InventoryAction {
+    Toggle,
+    Close,
+    Up,
+    Down,
+    Left,
+    Right,
+    Confirm,
+    Drop,
+}
+
+fn make_input_map() -> InputMap<InventoryAction> {
+    InputMap::new([
+        (InventoryAction::Toggle, KeyCode::KeyI),
+        (InventoryAction::Toggle, KeyCode::Tab),
+        (InventoryAction::Close, KeyCode::Escape),
+        (InventoryAction::Up, KeyCode::ArrowUp),
+        (InventoryAction::Down, KeyCode::ArrowDown),
+        (InventoryAction::Left, KeyCode::ArrowLeft),
+        (InventoryAction::Right, KeyCode::ArrowRight),
+        (InventoryAction::Confirm, KeyCode::Enter),
+        (InventoryAction::Drop, KeyCode::KeyX),
+    ])
+        .with(InventoryAction::Toggle, GamepadButtonType::Select)
+        .with(InventoryAction::Close, GamepadButtonType::East)
+        .with(InventoryAction::Up, GamepadButtonType::DPadUp)
+        .with(InventoryAction::Down, GamepadButtonType::DPadDown)
+        .with(InventoryAction::Left, GamepadButtonType::DPadLeft)
+        .with(InventoryAction::Right, GamepadButtonType::DPadRight)
+        .with(InventoryAction::Confirm, GamepadButtonType::South)
+        .with(InventoryAction::Drop, GamepadButtonType::West)
+}
+
+/// A slot of the screen: inventory slots come first, then the equipment slots
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ScreenSlot {
+    Inventory(usize),
+    Equipment(EquipmentSlot),
+}
+
+#[derive(Resource, Debug, Default)]
+pub(crate) struct InventoryScreen {
+    pub open: bool,
+    /// Index of the selected slot, see [`ScreenSlot`]
+    pub selected: usize,
+}
+
+impl InventoryScreen {
+    fn slot(&self, inventory_len: usize) -> ScreenSlot {
+        match self.selected.checked_sub(inventory_len) {
+            None => ScreenSlot::Inventory(self.selected),
+            Some(index) =>
+                ScreenSlot::Equipment(EquipmentSlot::ALL[index % EquipmentSlot::ALL.len()]),
+        }
+    }
+}
+
+pub(crate) fn inventory_open(screen: Res<InventoryScreen>) -> bool {
+    screen.open
+}
+
+/// Root node of the screen
+#[derive(Component)]
+struct InventoryScreenRoot;
+
+#[derive(Component, Clone, Copy)]
+struct SlotButton(usize);
+
+#[derive(Component)]
+struct DetailsText;
+
+fn toggle_inventory_screen(
+    actions: Res<ActionState<InventoryAction>>,
+    mut screen: ResMut<InventoryScreen>,
+    mut time: ResMut<Time<Virtual>>,
+    mut rapier_config: ResMut<RapierConfiguration>
+) {
+    let open = if screen.open {
+        !(
+            actions.just_pressed(&InventoryAction::Toggle) ||
+            actions.just_pressed(&InventoryAction::Close)
+        )
+    } else {
+        actions.just_pressed(&InventoryAction::Toggle)
+    };
+    if open == screen.open {
+        return;
+    }
+    screen.open = open;
+    rapier_config.physics_pipeline_active = !open;
+    if open {
+        time.pause();
+    } else {
+        time.unpause();
+    }
+}
+
+/// Keeps the gameplay inputs from reaching the player while the screen is open
+fn consume_player_actions(mut query: Query<&mut ActionState<Action>, With<Player>>) {
+    for mut action_state in &mut query {
+        action_state.consume_all();
+    }
+}
+
+fn text_style(asset_server: &AssetServer) -> TextStyle {
+    TextStyle {
+        font: asset_server.load(FONT),
+        font_size: 10.0,
+        color: Color::WHITE,
+    }
+}
+
+fn spawn_slot(
+    builder: &mut UiBuilder<Entity>,
+    index: usize,
+    stack: Option<(&ItemId, u32)>,
+    registry: &ItemRegistry,
+    asset_server: &AssetServer,
+    text_style: &TextStyle
+) {
+    builder.container(
+        (
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(SLOT_SIZE),
+                    height: Val::Px(SLOT_SIZE),
+                    margin: UiRect::all(Val::Px(2.0)),
+                    justify_content: JustifyContent::End,
+                    align_items: AlignItems::End,
+                    ..default()
+                },
+                background_color: SLOT_COLOR.into(),
+                ..default()
+            },
+            SlotButton(index),
+        ),
+        |slot| {
+            let Some((item, quantity)) = stack else {
+                return;
+            };
+            if let Some(definition) = registry.get(item) {
+                slot.container(
+                    ImageBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            width: Val::Percent(100.0),
+                            height: Val::Percent(100.0),
+                            ..default()
+                        },
+                        image: UiImage::new(asset_server.load(&definition.icon)),
+                        focus_policy: FocusPolicy::Pass,
+                        ..default()
+                    },
+                    |_| {}
+                );
+            }
+            if quantity > 1 {
+                slot.label(LabelConfig::default())
+                    .entity_commands()
+                    .set_text(quantity.to_string(), Some(text_style.clone()));
+            }
+        }
+    );
+}
+
+/// Spawns the screen when opened, rebuilds it when the content changes and
+/// despawns it when closed
+fn build_inventory_screen(
+    mut commands: Commands,
+    screen: Res<InventoryScreen>,
+    players: Query<(&Inventory, &Equipment), With<Player>>,
+    changed: Query<(), (With<Player>, Or<(Changed<Inventory>, Changed<Equipment>)>)>,
+    roots: Query<Entity, With<InventoryScreenRoot>>,
+    items: Items,
+    asset_server: Res<AssetServer>
+) {
+    let spawned = !roots.is_empty();
+    if screen.open && spawned && changed.is_empty() {
+        return;
+    }
+    if !screen.open && !spawned {
+        return;
+    }
+    for root in &roots {
+        commands.entity(root).despawn_recursive();
+    }
+    let (true, Ok((inventory, equipment)), Some(registry)) = (
+        screen.open,
+        players.get_single(),
+        items.registry(),
+    ) else {
+        return;
+    };
+    let text_style = text_style(&asset_server);
+
+    commands.ui_builder(UiRoot).container(
+        (
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    column_gap: Val::Px(16.0),
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+            InventoryScreenRoot,
+            Name::new("inventory screen"),
+        ),
+        |root| {
+            root.container(
+                NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        row_gap: Val::Px(8.0),
+                        ..default()
+                    },
+                    ..default()
+                },
+                |column| {
+                    // Inventory grid
+                    column.container(
+                        NodeBundle {
+                            style: Style {
+                                flex_wrap: FlexWrap::Wrap,
+                                width: Val::Px((COLUMNS as f32) * (SLOT_SIZE + 4.0)),
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        |grid| {
+                            for (index, stack) in inventory.slots.iter().enumerate() {
+                                let stack = stack.as_ref().map(|stack| (&stack.item, stack.quantity));
+                                spawn_slot(grid, index, stack, registry, &asset_server, &text_style);
+                            }
+                        }
+                    );
+                    // Equipment slots
+                    column.container(NodeBundle::default(), |row| {
+                        for (index, slot) in EquipmentSlot::ALL.into_iter().enumerate() {
+                            let item = equipment.get(slot).map(|item| (item, 1));
+                            let index = inventory.slots.len() + index;
+                            spawn_slot(row, index, item, registry, &asset_server, &text_style);
+                        }
+                    });
+                }
+            );
+            root.container(
+                NodeBundle {
+                    style: Style {
+                        width: Val::Px(160.0),
+                        padding: UiRect::all(Val::Px(8.0)),
+                        ..default()
+                    },
+                    background_color: SLOT_COLOR.into(),
+                    ..default()
+                },
+                |details| {
+                    details
+                        .label(LabelConfig::default())
+                        .entity_commands()
+                        .insert(DetailsText)
+                        .set_text("", Some(text_style.clone()));
+                }
+            );
+        }
+    );
+}
+
+/// Moves the selection with the keyboard, the gamepad and the mouse hover
+fn navigate_inventory_screen(
+    actions: Res<ActionState<InventoryAction>>,
+    mut screen: ResMut<InventoryScreen>,
+    players: Query<&Inventory, With<Player>>,
+    hovered: Query<(&Interaction, &SlotButton), Changed<Interaction>>
+) {
+    let Ok(inventory) = players.get_single() else {
+        return;
+    };
+    let count = inventory.slots.len() + EquipmentSlot::ALL.len();
+    let mut selected = screen.selected as isize;
+    for (action, step) in [
+        (InventoryAction::Left, -1),
+        (InventoryAction::Right, 1),
+        (InventoryAction::Up, -(COLUMNS as isize)),
+        (InventoryAction::Down, COLUMNS as isize),
+    ] {
+        if actions.just_pressed(&action) {
+            selected += step;
+        }
+    }
+    for (interaction, slot) in &hovered {
+        if *interaction != Interaction::None {
+            selected = slot.0 as isize;
+        }
+    }
+    let selected = selected.clamp(0, (count as isize) - 1) as usize;
+    if screen.selected != selected {
+        screen.selected = selected;
+    }
+}
+
+fn describe(definition: &ItemDefinition, quantity: u32) -> String {
+    let mut description = format!("{} x{}\n{:?}", definition.name, quantity, definition.category);
+    if !definition.tags.is_empty() {
+        description += &format!("\n{}", definition.tags.join(", "));
+    }
+    if let Some(equipment) = definition.equipment {
+        let modifiers = equipment.modifiers;
+        description += &format!("\n\n{:?} slot", equipment.slot);
+        for (name, value) in [
+            ("Armor", modifiers.armor as f32),
+            ("Health", modifiers.max_health as f32),
+        ] {
+            if value != 0.0 {
+                description += &format!("\n{} {:+}", name, value);
+            }
+        }
+        for (name, value) in [
+            ("Speed", modifiers.move_speed),
+            ("Damage", modifiers.damage),
+        ] {
+            if value != 0.0 {
+                description += &format!("\n{} {:+}%", name, (value * 100.0).round());
+            }
+        }
+        description += "\n\n[Enter] Equip";
+    } else if definition.on_use.is_some() {
+        description += "\n\n[Enter] Use";
+    }
+    description + "\n[X] Drop"
+}
+
+/// Highlights the selected slot and shows the details of its item
+fn update_selection(
+    mut commands: Commands,
+    screen: Res<InventoryScreen>,
+    mut slots: Query<(&SlotButton, &mut BackgroundColor)>,
+    added_slots: Query<(), Added<SlotButton>>,
+    details: Query<Entity, With<DetailsText>>,
+    players: Query<(&Inventory, &Equipment), With<Player>>,
+    items: Items,
+    asset_server: Res<AssetServer>
+) {
+    if !screen.is_changed() && added_slots.is_empty() {
+        return;
+    }
+    for (slot, mut color) in &mut slots {
+        *color = (if slot.0 == screen.selected { SELECTED_SLOT_COLOR } else { SLOT_COLOR }).into();
+    }
+    let (Ok((inventory, equipment)), Some(registry)) = (
+        players.get_single(),
+        items.registry(),
+    ) else {
+        return;
+    };
+    let stack = match screen.slot(inventory.slots.len()) {
+        ScreenSlot::Inventory(index) =>
+            inventory.slots
+                .get(index)
+                .and_then(Option::as_ref)
+                .map(|stack| (&stack.item, stack.quantity)),
+        ScreenSlot::Equipment(slot) => equipment.get(slot).map(|item| (item, 1)),
+    };
+    let text = stack
+        .and_then(|(item, quantity)| Some(describe(registry.get(item)?, quantity)))
+        .unwrap_or_default();
+    for entity in &details {
+        commands.entity(entity).set_text(text.clone(), Some(text_style(&asset_server)));
+    }
+}
+
+/// Equips, uses or drops the selected item
+fn use_selected_item(
+    actions: Res<ActionState<InventoryAction>>,
+    screen: Res<InventoryScreen>,
+    clicked: Query<&Interaction, (Changed<Interaction>, With<SlotButton>)>,
+    mut players: Query<
+        (Entity, &mut Inventory, &mut Equipment, &GlobalTransform, Option<&Facing>),
+        With<Player>
+    >,
+    mut use_events: EventWriter<UseItem>,
+    mut drop_events: EventWriter<SpawnPickup>,
+    items: Items
+) {
+    let confirm =
+        actions.just_pressed(&InventoryAction::Confirm) ||
+        clicked.iter().any(|interaction| *interaction == Interaction::Pressed);
+    let drop = actions.just_pressed(&InventoryAction::Drop);
+    if !confirm && !drop {
+        return;
+    }
+    let (Ok((player, mut inventory, mut equipment, transform, facing)), Some(registry)) = (
+        players.get_single_mut(),
+        items.registry(),
+    ) else {
+        return;
+    };
+    let direction = facing.map_or(Vec2::X, |facing| facing.0);
+    let result = match screen.slot(inventory.slots.len()) {
+        ScreenSlot::Equipment(slot) if drop => {
+            if let Some(item) = equipment.slots.remove(&slot) {
+                drop_events.send(SpawnPickup {
+                    item,
+                    quantity: 1,
+                    position: transform.translation().truncate(),
+                    velocity: direction * 120.0 + Vec2::Y * 120.0,
+                    dropped_by: Some(player),
+                });
+            }
+            Ok(())
+        }
+        ScreenSlot::Equipment(slot) => equipment.unequip(&mut inventory, registry, slot).map(|_| ()),
+        ScreenSlot::Inventory(index) if drop => {
+            inventory.take_slot(index).map(|stack| {
+                if let Some(stack) = stack {
+                    drop_events.send(SpawnPickup {
+                        item: stack.item,
+                        quantity: stack.quantity,
+                        position: transform.translation().truncate(),
+                        velocity: direction * 120.0 + Vec2::Y * 120.0,
+                        dropped_by: Some(player),
+                    });
+                }
+            })
+        }
+        ScreenSlot::Inventory(index) => {
+            let Some(stack) = inventory.slots.get(index).cloned().flatten() else {
+                return;
+            };
+            let Some(definition) = registry.get(&stack.item) else {
+                return;
+            };
+            if definition.equipment.is_some() {
+                equipment.equip(&mut inventory, registry, &stack.item)
+            } else {
+                if definition.on_use.is_some() {
+                    use_events.send(UseItem {
+                        user: player,
+                        item: stack.item,
+                    });
+                }
+                Ok(())
+            }
+        }
+    };
+    if let Err(error) = result {
+        info!("Inventory action failed: {}", error);
+    }
+}
+
+pub(crate) fn plugin(app: &mut App) {
+    app.add_plugins(InputManagerPlugin::<InventoryAction>::default())
+        .init_resource::<ActionState<InventoryAction>>()
+        .insert_resource(make_input_map())
+        .init_resource::<InventoryScreen>()
+        .add_systems(
+            PreUpdate,
+            consume_player_actions
+                .after(InputManagerSystem::Update)
+                .before(InputManagerSystem::ManualControl)
+                .run_if(inventory_open)
+        )
+        .add_systems(
+            Update,
+            (
+                toggle_inventory_screen.run_if(not_in_dialogue),
+                build_inventory_screen,
+                (navigate_inventory_screen, use_selected_item, update_selection).run_if(
+                    inventory_open
+                ),
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing))
+        );
+}
@@ -12,6 +12,7 @@ use aery::prelude::*;
 pub(crate) mod banner_widget;
 
 pub(crate) mod fps_widget;
+pub(crate) mod inventory_screen;
 // Rewrite with shapes?
 //pub(crate) mod keycap;
 //pub(crate) mod keycap_demo;
@@ -36,7 +37,7 @@ pub(crate) fn plugin(app: &mut bevy::app::App) {
     // Resources
     app.insert_resource(ClearColor(Color::DARK_GRAY))
         // Plugins
-        .add_plugins(ShapePlugin::default())
+        .add_plugins((ShapePlugin::default(), inventory_screen::plugin))
         // 🐺
         // Startup
         //.add_systems(Startup, keycap_demo)
@@ -4,15 +4,21 @@ use bevy::{ prelude::*, render::primitives::Aabb, sprite::Anchor };
 #[derive(Component)]
 pub(super) struct Tooltip;
 
-use crate::components::interactions::{ InteractionSensor, Interactive };
+use crate::{
+    components::{ interactions::{ InteractionSensor, Interactive }, pet::{ Befriendable, Pet } },
+    entities::{ cauldron::{ Cauldron, CauldronState, Cauldrons }, chest::Chest },
+};
 pub(super) fn spawn_tooltip(
     mut commands: Commands,
-    sensors: Query<&InteractionSensor, Changed<InteractionSensor>>,
-    interactives: Query<&Aabb, With<Interactive>>,
+    sensors: Query<Ref<InteractionSensor>>,
+    interactives: Query<
+        (&Aabb, &Interactive, Has<Chest>, Option<&Cauldron>, Has<Befriendable>, Has<Pet>)
+    >,
     tooltips: Query<Entity, With<Tooltip>>,
+    cauldrons: Res<Cauldrons>,
     asset_server: Res<AssetServer>
 ) {
-    if sensors.is_empty() {
+    if !cauldrons.is_changed() && !sensors.iter().any(|sensor| sensor.is_changed()) {
         return; // Don't do anything if the InteractionSensor didn't change
     }
     // Remove old tooltips
@@ -23,11 +29,23 @@ pub(super) fn spawn_tooltip(
         let Some(entity) = sensor.closest_entity else {
             continue;
         };
-        let Ok(aabb) = interactives.get(entity) else {
+        let Ok((aabb, interactive, is_chest, cauldron, befriendable, is_pet)) = interactives.get(entity) else {
             continue;
         };
         let transform = Transform::from_translation(Vec3::Y * (aabb.half_extents.y + 5.0));
-        let text = Text::from_section("E to talk", TextStyle {
+        let cauldron = cauldron.map(|cauldron| cauldrons.0.get(&cauldron.iid));
+        let prompt = match cauldron {
+            _ if is_chest => "E to open".to_string(),
+            _ if is_pet => format!("F to command {}", interactive.name),
+            _ if befriendable => format!("E to befriend {}", interactive.name),
+            Some(Some(CauldronState { ready: Some(_), .. })) => "E to collect".to_string(),
+            Some(Some(CauldronState { brewing: Some(_), .. })) => "Brewing...".to_string(),
+            Some(Some(CauldronState { selected: Some(recipe), .. })) =>
+                format!("E to brew {}, G to change", recipe),
+            Some(_) => "E to brew, G to pick a recipe".to_string(),
+            None => "E to talk".to_string(),
+        };
+        let text = Text::from_section(prompt, TextStyle {
             font: asset_server.load("fonts/bahnschrift.ttf"),
             font_size: 12.0,
             ..default()
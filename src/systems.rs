@@ -21,6 +21,18 @@ pub fn setup_world_systems(app: &mut App) {
     // ⚠️ FIXME: some parts should be moved to the ldtk plugin once we figure the execution order of the systems.
     app.add_plugins((
         crate::entities::plugin,
+        components::health::plugin,
+        components::status_effects::plugin,
+        components::projectile::plugin,
+        components::hitbox::plugin,
+        components::shapeshift::plugin,
+        components::pickup::plugin,
+        components::loot::plugin,
+        components::items::plugin,
+        components::equipment::plugin,
+        components::crafting::plugin,
+        components::ai::plugin,
+        (components::hearing::plugin, components::animals::plugin, components::pet::plugin, components::schedule::plugin),
         LdtkPlugin,
         RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0),
     ))
@@ -63,9 +75,11 @@ pub fn setup_world_systems(app: &mut App) {
                 ).chain(),
                 components::swimming::detect_swim_range,
                 components::predefinedpath::move_on_path,
+                components::facing::update_facing,
                 components::items::dbg_player_items,
-                components::line_of_sight::line_of_sight::<entities::Player>,
+                components::line_of_sight::line_of_sight::<With<entities::Player>>,
                 entities::player::draw_health_bar,
+                entities::enemy::despawn_dead_enemies.after(components::loot::drop_loot_on_death),
             ).run_if(in_state(GameState::Playing))
         )
 